@@ -1,29 +1,73 @@
 package main
 
 import (
+	"bufio"
 	"encoding/json"
 	"fmt"
 	"log"
 	"net"
 	"os"
+	"sync"
 
 	"github.com/getlantern/systray"
 )
 
+// Messages are newline-delimited JSON objects with a "type" field.
+type Message struct {
+	Type string `json:"type"`
+}
+
+type BreakReminder struct {
+	ID            int64   `json:"id"`
+	ActiveMinutes float64 `json:"active_minutes"`
+}
+
+type BreakAction struct {
+	Type string `json:"type"`
+	ID   int64  `json:"id"`
+}
+
 type Metrics struct {
-	Keypresses      int     `json:"keypresses"`
-	MouseClicks     int     `json:"mouse_clicks"`
+	Keypresses      int64   `json:"keypresses"`
+	MouseClicks     int64   `json:"mouse_clicks"`
 	MouseDistanceIn float64 `json:"mouse_distance_in"`
 	MouseDistanceMi float64 `json:"mouse_distance_mi"`
-	ScrollSteps     int     `json:"scroll_steps"`
+	ScrollSteps     int64   `json:"scroll_steps"`
+	LeftClicks      int64   `json:"left_clicks"`
+	RightClicks     int64   `json:"right_clicks"`
+	MiddleClicks    int64   `json:"middle_clicks"`
+	ExtraClicks     int64   `json:"extra_clicks"`
+	DoubleClicks    int64   `json:"double_clicks"`
+	TripleClicks    int64   `json:"triple_clicks"`
+	Drags           int64   `json:"drags"`
+	DragDistanceIn  float64 `json:"drag_distance_in"`
+	ActiveMinutes   float64 `json:"active_minutes"`
+	TypingAvgWpm    float64 `json:"typing_avg_wpm"`
+	TypingPeakWpm1m float64 `json:"typing_peak_wpm_1m"`
+	TypingPeakWpm5m float64 `json:"typing_peak_wpm_5m"`
 }
 
 var (
 	mKeyPresses    *systray.MenuItem
 	mMouseClicks   *systray.MenuItem
+	mClickButtons  *systray.MenuItem
+	mMultiClicks   *systray.MenuItem
+	mDrags         *systray.MenuItem
 	mMouseDistance *systray.MenuItem
 	mScrollSteps   *systray.MenuItem
+	mActiveTime    *systray.MenuItem
+	mTypingSpeed   *systray.MenuItem
+	mTypingPeak    *systray.MenuItem
+	mBreak         *systray.MenuItem
+	mBreakSnooze   *systray.MenuItem
+	mBreakSkip     *systray.MenuItem
 	listener       net.Listener
+
+	// The logger's connection, used to send break actions back.
+	connMu      sync.Mutex
+	activeConn  net.Conn
+	breakMu     sync.Mutex
+	activeBreak int64
 )
 
 const sockAddr = "/tmp/kawaiilogger.sock"
@@ -76,22 +120,69 @@ func connectToSocket() {
 }
 
 func handleConnection(conn net.Conn) {
-	buffer := make([]byte, 1024)
-	for {
-		n, err := conn.Read(buffer)
-		if err != nil {
-			log.Printf("Error reading from socket: %v\n", err)
-			return
-		}
+	connMu.Lock()
+	activeConn = conn
+	connMu.Unlock()
+
+	scanner := bufio.NewScanner(conn)
+	for scanner.Scan() {
+		line := scanner.Bytes()
 
-		var metrics Metrics
-		if err := json.Unmarshal(buffer[:n], &metrics); err != nil {
-			log.Printf("Error unmarshaling metrics: %v\n", err)
+		var message Message
+		if err := json.Unmarshal(line, &message); err != nil {
+			log.Printf("Error unmarshaling message: %v\n", err)
 			continue
 		}
 
-		log.Printf("Received metrics: %+v\n", metrics)
-		updateMenuItems(&metrics)
+		switch message.Type {
+		case "metrics":
+			var metrics Metrics
+			if err := json.Unmarshal(line, &metrics); err != nil {
+				log.Printf("Error unmarshaling metrics: %v\n", err)
+				continue
+			}
+			log.Printf("Received metrics: %+v\n", metrics)
+			updateMenuItems(&metrics)
+		case "break_reminder":
+			var reminder BreakReminder
+			if err := json.Unmarshal(line, &reminder); err != nil {
+				log.Printf("Error unmarshaling break reminder: %v\n", err)
+				continue
+			}
+			showBreakReminder(&reminder)
+		case "break_cleared":
+			clearBreakReminder()
+		default:
+			log.Printf("Ignoring message of type %q\n", message.Type)
+		}
+	}
+	if err := scanner.Err(); err != nil {
+		log.Printf("Error reading from socket: %v\n", err)
+	}
+}
+
+func sendBreakAction(actionType string) {
+	breakMu.Lock()
+	id := activeBreak
+	breakMu.Unlock()
+	if id == 0 {
+		return
+	}
+
+	data, err := json.Marshal(BreakAction{Type: actionType, ID: id})
+	if err != nil {
+		log.Printf("Error marshaling break action: %v\n", err)
+		return
+	}
+
+	connMu.Lock()
+	defer connMu.Unlock()
+	if activeConn == nil {
+		log.Println("No logger connection, dropping break action")
+		return
+	}
+	if _, err := activeConn.Write(append(data, '\n')); err != nil {
+		log.Printf("Error sending break action: %v\n", err)
 	}
 }
 
@@ -102,12 +193,35 @@ func onReady() {
 
 	mKeyPresses = systray.AddMenuItem("Keypresses: 0", "Number of keypresses")
 	mMouseClicks = systray.AddMenuItem("Mouse Clicks: 0", "Number of mouse clicks")
+	mClickButtons = mMouseClicks.AddSubMenuItem("Left 0 / Right 0 / Middle 0 / Extra 0", "Clicks per mouse button")
+	mMultiClicks = mMouseClicks.AddSubMenuItem("Double 0 / Triple 0", "Double and triple clicks")
+	mDrags = mMouseClicks.AddSubMenuItem("Drags: 0 (0 in)", "Press-move-release drags")
 	mMouseDistance = systray.AddMenuItem("Mouse Travel: 0 in / 0 mi", "Distance moved by mouse")
 	mScrollSteps = systray.AddMenuItem("Scroll Steps: 0", "Number of scroll steps")
+	mActiveTime = systray.AddMenuItem("Active Today: 0h 0m", "Time spent actively using the computer today")
+	mTypingSpeed = systray.AddMenuItem("Typing Today: 0 WPM avg", "Today's average typing speed")
+	mTypingPeak = mTypingSpeed.AddSubMenuItem("Peak: 0 WPM (1 min) / 0 WPM (5 min)", "Today's fastest sustained typing")
+
+	systray.AddSeparator()
+	mBreak = systray.AddMenuItem("Time for a break", "You've been active for a while")
+	mBreakSnooze = mBreak.AddSubMenuItem("Snooze", "Remind me again later")
+	mBreakSkip = mBreak.AddSubMenuItem("Skip", "Skip this break")
+	mBreak.Hide()
 
 	systray.AddSeparator()
 	mQuit := systray.AddMenuItem("Quit", "Quit the application")
 
+	go func() {
+		for {
+			select {
+			case <-mBreakSnooze.ClickedCh:
+				sendBreakAction("snooze_break")
+			case <-mBreakSkip.ClickedCh:
+				sendBreakAction("skip_break")
+			}
+		}
+	}()
+
 	go func() {
 		<-mQuit.ClickedCh
 		log.Println("Quit clicked, cleaning up...")
@@ -133,20 +247,58 @@ func cleanup() {
 	os.Remove(sockAddr)
 }
 
+func showBreakReminder(reminder *BreakReminder) {
+	if !isMenuInitialized || mBreak == nil {
+		log.Println("Menu items not initialized, skipping break reminder")
+		return
+	}
+
+	breakMu.Lock()
+	activeBreak = reminder.ID
+	breakMu.Unlock()
+
+	mBreak.SetTitle(fmt.Sprintf("Time for a break (%.0f min active)", reminder.ActiveMinutes))
+	mBreak.Show()
+	systray.SetTitle("☕")
+}
+
+func clearBreakReminder() {
+	breakMu.Lock()
+	activeBreak = 0
+	breakMu.Unlock()
+
+	if !isMenuInitialized || mBreak == nil {
+		return
+	}
+	mBreak.Hide()
+	systray.SetTitle("📊")
+}
+
 func updateMenuItems(metrics *Metrics) {
 	if !isMenuInitialized {
 		log.Println("Menu items not initialized, skipping update")
 		return
 	}
 
-	if mKeyPresses == nil || mMouseClicks == nil || mMouseDistance == nil || mScrollSteps == nil {
+	if mKeyPresses == nil || mMouseClicks == nil || mMouseDistance == nil || mScrollSteps == nil ||
+		mClickButtons == nil || mMultiClicks == nil || mDrags == nil || mActiveTime == nil ||
+		mTypingSpeed == nil || mTypingPeak == nil {
 		log.Println("Menu items are nil, skipping update")
 		return
 	}
 
 	mKeyPresses.SetTitle(fmt.Sprintf("Keypresses: %d", metrics.Keypresses))
 	mMouseClicks.SetTitle(fmt.Sprintf("Mouse Clicks: %d", metrics.MouseClicks))
+	mClickButtons.SetTitle(fmt.Sprintf("Left %d / Right %d / Middle %d / Extra %d",
+		metrics.LeftClicks, metrics.RightClicks, metrics.MiddleClicks, metrics.ExtraClicks))
+	mMultiClicks.SetTitle(fmt.Sprintf("Double %d / Triple %d", metrics.DoubleClicks, metrics.TripleClicks))
+	mDrags.SetTitle(fmt.Sprintf("Drags: %d (%.2f in)", metrics.Drags, metrics.DragDistanceIn))
 	mMouseDistance.SetTitle(fmt.Sprintf("Mouse Travel: %.2f in / %.2f mi",
 		metrics.MouseDistanceIn, metrics.MouseDistanceMi))
 	mScrollSteps.SetTitle(fmt.Sprintf("Scroll Steps: %d", metrics.ScrollSteps))
+	activeMinutes := int(metrics.ActiveMinutes)
+	mActiveTime.SetTitle(fmt.Sprintf("Active Today: %dh %dm", activeMinutes/60, activeMinutes%60))
+	mTypingSpeed.SetTitle(fmt.Sprintf("Typing Today: %.0f WPM avg", metrics.TypingAvgWpm))
+	mTypingPeak.SetTitle(fmt.Sprintf("Peak: %.0f WPM (1 min) / %.0f WPM (5 min)",
+		metrics.TypingPeakWpm1m, metrics.TypingPeakWpm5m))
 }
@@ -0,0 +1,55 @@
+-- Same schema as migrations/sqlite, in PostgreSQL types. Timestamps are
+-- UTC without a zone, like SQLite's CURRENT_TIMESTAMP.
+
+CREATE TABLE metrics (
+    id BIGSERIAL PRIMARY KEY,
+    timestamp TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc'),
+    keypresses INTEGER NOT NULL DEFAULT 0,
+    mouse_clicks INTEGER NOT NULL DEFAULT 0,
+    mouse_distance_in DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    mouse_distance_mi DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    scroll_steps INTEGER NOT NULL DEFAULT 0,
+    scroll_up INTEGER NOT NULL DEFAULT 0,
+    scroll_down INTEGER NOT NULL DEFAULT 0,
+    scroll_left INTEGER NOT NULL DEFAULT 0,
+    scroll_right INTEGER NOT NULL DEFAULT 0,
+    scroll_vertical_precise DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    scroll_horizontal_precise DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    left_clicks INTEGER NOT NULL DEFAULT 0,
+    right_clicks INTEGER NOT NULL DEFAULT 0,
+    middle_clicks INTEGER NOT NULL DEFAULT 0,
+    extra_clicks INTEGER NOT NULL DEFAULT 0,
+    double_clicks INTEGER NOT NULL DEFAULT 0,
+    triple_clicks INTEGER NOT NULL DEFAULT 0,
+    drags INTEGER NOT NULL DEFAULT 0,
+    drag_distance_in DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    typing_keys INTEGER NOT NULL DEFAULT 0,
+    typing_seconds DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    typing_kpm DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    typing_wpm DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    peak_kpm_1m DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    peak_kpm_5m DOUBLE PRECISION NOT NULL DEFAULT 0.0
+);
+
+CREATE INDEX idx_metrics_timestamp ON metrics (timestamp);
+
+CREATE TABLE key_counts (
+    metrics_id BIGINT NOT NULL REFERENCES metrics (id) ON DELETE CASCADE,
+    keycode TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY (metrics_id, keycode)
+);
+
+CREATE TABLE activity_sessions (
+    id BIGSERIAL PRIMARY KEY,
+    start_time TIMESTAMP NOT NULL UNIQUE,
+    end_time TIMESTAMP NOT NULL
+);
+
+CREATE TABLE break_reminders (
+    id BIGSERIAL PRIMARY KEY,
+    reminded_at TIMESTAMP NOT NULL,
+    active_minutes DOUBLE PRECISION NOT NULL,
+    outcome TEXT NOT NULL,
+    resolved_at TIMESTAMP
+);
@@ -0,0 +1,67 @@
+-- All-time totals in a single row, kept up to date as intervals are saved
+-- so reading them doesn't scan history. Seeded from the daily rollups,
+-- which hold everything saved so far.
+
+CREATE TABLE metrics_totals (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    samples INTEGER NOT NULL DEFAULT 0,
+    keypresses INTEGER NOT NULL DEFAULT 0,
+    mouse_clicks INTEGER NOT NULL DEFAULT 0,
+    mouse_distance_in DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    mouse_distance_mi DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    scroll_steps INTEGER NOT NULL DEFAULT 0,
+    scroll_up INTEGER NOT NULL DEFAULT 0,
+    scroll_down INTEGER NOT NULL DEFAULT 0,
+    scroll_left INTEGER NOT NULL DEFAULT 0,
+    scroll_right INTEGER NOT NULL DEFAULT 0,
+    scroll_vertical_precise DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    scroll_horizontal_precise DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    left_clicks INTEGER NOT NULL DEFAULT 0,
+    right_clicks INTEGER NOT NULL DEFAULT 0,
+    middle_clicks INTEGER NOT NULL DEFAULT 0,
+    extra_clicks INTEGER NOT NULL DEFAULT 0,
+    double_clicks INTEGER NOT NULL DEFAULT 0,
+    triple_clicks INTEGER NOT NULL DEFAULT 0,
+    drags INTEGER NOT NULL DEFAULT 0,
+    drag_distance_in DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    typing_keys INTEGER NOT NULL DEFAULT 0,
+    typing_seconds DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    peak_kpm_1m DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    peak_kpm_5m DOUBLE PRECISION NOT NULL DEFAULT 0.0
+);
+
+INSERT INTO metrics_totals (
+    id, samples, keypresses, mouse_clicks, mouse_distance_in,
+    mouse_distance_mi, scroll_steps, scroll_up, scroll_down,
+    scroll_left, scroll_right, scroll_vertical_precise,
+    scroll_horizontal_precise, left_clicks, right_clicks, middle_clicks,
+    extra_clicks, double_clicks, triple_clicks, drags, drag_distance_in,
+    typing_keys, typing_seconds, peak_kpm_1m, peak_kpm_5m
+)
+SELECT
+    1,
+    COALESCE(SUM(samples), 0),
+    COALESCE(SUM(keypresses), 0),
+    COALESCE(SUM(mouse_clicks), 0),
+    COALESCE(SUM(mouse_distance_in), 0),
+    COALESCE(SUM(mouse_distance_mi), 0),
+    COALESCE(SUM(scroll_steps), 0),
+    COALESCE(SUM(scroll_up), 0),
+    COALESCE(SUM(scroll_down), 0),
+    COALESCE(SUM(scroll_left), 0),
+    COALESCE(SUM(scroll_right), 0),
+    COALESCE(SUM(scroll_vertical_precise), 0),
+    COALESCE(SUM(scroll_horizontal_precise), 0),
+    COALESCE(SUM(left_clicks), 0),
+    COALESCE(SUM(right_clicks), 0),
+    COALESCE(SUM(middle_clicks), 0),
+    COALESCE(SUM(extra_clicks), 0),
+    COALESCE(SUM(double_clicks), 0),
+    COALESCE(SUM(triple_clicks), 0),
+    COALESCE(SUM(drags), 0),
+    COALESCE(SUM(drag_distance_in), 0),
+    COALESCE(SUM(typing_keys), 0),
+    COALESCE(SUM(typing_seconds), 0),
+    COALESCE(MAX(peak_kpm_1m), 0.0),
+    COALESCE(MAX(peak_kpm_5m), 0.0)
+FROM metrics_daily;
@@ -0,0 +1,83 @@
+-- Counters were 32-bit and an all-time keypress total overflows around
+-- 2.1 billion. SQLite's INTEGER is already 64-bit, so only Postgres needs
+-- widening.
+
+ALTER TABLE metrics
+    ALTER COLUMN keypresses TYPE BIGINT,
+    ALTER COLUMN mouse_clicks TYPE BIGINT,
+    ALTER COLUMN scroll_steps TYPE BIGINT,
+    ALTER COLUMN scroll_up TYPE BIGINT,
+    ALTER COLUMN scroll_down TYPE BIGINT,
+    ALTER COLUMN scroll_left TYPE BIGINT,
+    ALTER COLUMN scroll_right TYPE BIGINT,
+    ALTER COLUMN left_clicks TYPE BIGINT,
+    ALTER COLUMN right_clicks TYPE BIGINT,
+    ALTER COLUMN middle_clicks TYPE BIGINT,
+    ALTER COLUMN extra_clicks TYPE BIGINT,
+    ALTER COLUMN double_clicks TYPE BIGINT,
+    ALTER COLUMN triple_clicks TYPE BIGINT,
+    ALTER COLUMN drags TYPE BIGINT,
+    ALTER COLUMN typing_keys TYPE BIGINT;
+
+ALTER TABLE key_counts
+    ALTER COLUMN count TYPE BIGINT;
+
+ALTER TABLE metrics_hourly
+    ALTER COLUMN samples TYPE BIGINT,
+    ALTER COLUMN keypresses TYPE BIGINT,
+    ALTER COLUMN mouse_clicks TYPE BIGINT,
+    ALTER COLUMN scroll_steps TYPE BIGINT,
+    ALTER COLUMN scroll_up TYPE BIGINT,
+    ALTER COLUMN scroll_down TYPE BIGINT,
+    ALTER COLUMN scroll_left TYPE BIGINT,
+    ALTER COLUMN scroll_right TYPE BIGINT,
+    ALTER COLUMN left_clicks TYPE BIGINT,
+    ALTER COLUMN right_clicks TYPE BIGINT,
+    ALTER COLUMN middle_clicks TYPE BIGINT,
+    ALTER COLUMN extra_clicks TYPE BIGINT,
+    ALTER COLUMN double_clicks TYPE BIGINT,
+    ALTER COLUMN triple_clicks TYPE BIGINT,
+    ALTER COLUMN drags TYPE BIGINT,
+    ALTER COLUMN typing_keys TYPE BIGINT;
+
+ALTER TABLE metrics_daily
+    ALTER COLUMN samples TYPE BIGINT,
+    ALTER COLUMN keypresses TYPE BIGINT,
+    ALTER COLUMN mouse_clicks TYPE BIGINT,
+    ALTER COLUMN scroll_steps TYPE BIGINT,
+    ALTER COLUMN scroll_up TYPE BIGINT,
+    ALTER COLUMN scroll_down TYPE BIGINT,
+    ALTER COLUMN scroll_left TYPE BIGINT,
+    ALTER COLUMN scroll_right TYPE BIGINT,
+    ALTER COLUMN left_clicks TYPE BIGINT,
+    ALTER COLUMN right_clicks TYPE BIGINT,
+    ALTER COLUMN middle_clicks TYPE BIGINT,
+    ALTER COLUMN extra_clicks TYPE BIGINT,
+    ALTER COLUMN double_clicks TYPE BIGINT,
+    ALTER COLUMN triple_clicks TYPE BIGINT,
+    ALTER COLUMN drags TYPE BIGINT,
+    ALTER COLUMN typing_keys TYPE BIGINT;
+
+ALTER TABLE metrics_totals
+    ALTER COLUMN samples TYPE BIGINT,
+    ALTER COLUMN keypresses TYPE BIGINT,
+    ALTER COLUMN mouse_clicks TYPE BIGINT,
+    ALTER COLUMN scroll_steps TYPE BIGINT,
+    ALTER COLUMN scroll_up TYPE BIGINT,
+    ALTER COLUMN scroll_down TYPE BIGINT,
+    ALTER COLUMN scroll_left TYPE BIGINT,
+    ALTER COLUMN scroll_right TYPE BIGINT,
+    ALTER COLUMN left_clicks TYPE BIGINT,
+    ALTER COLUMN right_clicks TYPE BIGINT,
+    ALTER COLUMN middle_clicks TYPE BIGINT,
+    ALTER COLUMN extra_clicks TYPE BIGINT,
+    ALTER COLUMN double_clicks TYPE BIGINT,
+    ALTER COLUMN triple_clicks TYPE BIGINT,
+    ALTER COLUMN drags TYPE BIGINT,
+    ALTER COLUMN typing_keys TYPE BIGINT;
+
+ALTER TABLE key_counts_hourly
+    ALTER COLUMN count TYPE BIGINT;
+
+ALTER TABLE key_counts_daily
+    ALTER COLUMN count TYPE BIGINT;
@@ -0,0 +1,11 @@
+-- Intervals waiting to be uploaded to Supabase, written in the same
+-- transaction as the interval itself and deleted once the upload succeeds.
+-- The payload is a copy of the interval so it outlives retention.
+
+CREATE TABLE sync_outbox (
+    id BIGSERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL,
+    payload TEXT NOT NULL,
+    attempts BIGINT NOT NULL DEFAULT 0,
+    last_error TEXT
+);
@@ -0,0 +1,18 @@
+-- The client-generated id of each interval and when it started; it ends at
+-- its timestamp. NULL for intervals saved before they were recorded.
+
+ALTER TABLE metrics ADD COLUMN interval_id TEXT;
+ALTER TABLE metrics ADD COLUMN interval_start TIMESTAMP;
+
+CREATE UNIQUE INDEX idx_metrics_interval_id ON metrics (interval_id);
+
+-- Intervals already waiting in the sync outbox get an id too. Their span
+-- isn't known, so it's taken to be the moment they were saved.
+UPDATE sync_outbox SET payload = (
+    payload::jsonb || jsonb_build_object(
+        'id', gen_random_uuid(),
+        'start', to_char(created_at, 'YYYY-MM-DD"T"HH24:MI:SS"Z"'),
+        'end', to_char(created_at, 'YYYY-MM-DD"T"HH24:MI:SS"Z"')
+    )
+)::text
+WHERE (payload::jsonb -> 'id') IS NULL;
@@ -0,0 +1,12 @@
+-- Where syncing to Supabase stands, in a single row. Intervals saved from
+-- started_at on are queued for upload as they're saved; the history before
+-- it is only uploaded by a backfill, which has got as far as
+-- backfilled_until.
+
+CREATE TABLE sync_state (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    started_at TIMESTAMP,
+    backfilled_until TIMESTAMP
+);
+
+INSERT INTO sync_state (id) VALUES (1);
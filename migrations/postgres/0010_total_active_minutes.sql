@@ -0,0 +1,11 @@
+-- Active minutes join the running totals, so reading the totals doesn't
+-- sum every session ever saved. Seeded from the sessions saved so far.
+
+ALTER TABLE metrics_totals ADD COLUMN active_minutes DOUBLE PRECISION NOT NULL DEFAULT 0;
+
+UPDATE metrics_totals
+SET active_minutes = (
+    SELECT CAST(COALESCE(SUM(EXTRACT(EPOCH FROM end_time - start_time)), 0) / 60.0 AS DOUBLE PRECISION)
+    FROM activity_sessions
+)
+WHERE id = 1;
@@ -0,0 +1,56 @@
+-- Schema as of the first versioned release. Databases created before
+-- migrations existed are brought up to this shape before it runs, so every
+-- statement here must tolerate existing objects.
+
+CREATE TABLE IF NOT EXISTS metrics (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+    keypresses INTEGER,
+    mouse_clicks INTEGER,
+    mouse_distance_in REAL,
+    mouse_distance_mi REAL,
+    scroll_steps INTEGER,
+    scroll_up INTEGER NOT NULL DEFAULT 0,
+    scroll_down INTEGER NOT NULL DEFAULT 0,
+    scroll_left INTEGER NOT NULL DEFAULT 0,
+    scroll_right INTEGER NOT NULL DEFAULT 0,
+    scroll_vertical_precise REAL NOT NULL DEFAULT 0.0,
+    scroll_horizontal_precise REAL NOT NULL DEFAULT 0.0,
+    left_clicks INTEGER NOT NULL DEFAULT 0,
+    right_clicks INTEGER NOT NULL DEFAULT 0,
+    middle_clicks INTEGER NOT NULL DEFAULT 0,
+    extra_clicks INTEGER NOT NULL DEFAULT 0,
+    double_clicks INTEGER NOT NULL DEFAULT 0,
+    triple_clicks INTEGER NOT NULL DEFAULT 0,
+    drags INTEGER NOT NULL DEFAULT 0,
+    drag_distance_in REAL NOT NULL DEFAULT 0.0,
+    typing_keys INTEGER NOT NULL DEFAULT 0,
+    typing_seconds REAL NOT NULL DEFAULT 0.0,
+    typing_kpm REAL NOT NULL DEFAULT 0.0,
+    typing_wpm REAL NOT NULL DEFAULT 0.0,
+    peak_kpm_1m REAL NOT NULL DEFAULT 0.0,
+    peak_kpm_5m REAL NOT NULL DEFAULT 0.0
+);
+
+CREATE INDEX IF NOT EXISTS idx_metrics_timestamp ON metrics (timestamp);
+
+CREATE TABLE IF NOT EXISTS key_counts (
+    metrics_id INTEGER NOT NULL REFERENCES metrics (id) ON DELETE CASCADE,
+    keycode TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY (metrics_id, keycode)
+);
+
+CREATE TABLE IF NOT EXISTS activity_sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    start_time DATETIME NOT NULL UNIQUE,
+    end_time DATETIME NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS break_reminders (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    reminded_at DATETIME NOT NULL,
+    active_minutes REAL NOT NULL,
+    outcome TEXT NOT NULL,
+    resolved_at DATETIME
+);
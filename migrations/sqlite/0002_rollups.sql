@@ -0,0 +1,163 @@
+-- Hourly and daily totals of metrics, keyed by the UTC start of each
+-- bucket. They are kept up to date as intervals are saved, so raw rows can
+-- be compacted away once they are old. Existing rows are rolled up here.
+
+CREATE TABLE metrics_hourly (
+    bucket_start DATETIME PRIMARY KEY,
+    samples INTEGER NOT NULL DEFAULT 0,
+    keypresses INTEGER NOT NULL DEFAULT 0,
+    mouse_clicks INTEGER NOT NULL DEFAULT 0,
+    mouse_distance_in REAL NOT NULL DEFAULT 0.0,
+    mouse_distance_mi REAL NOT NULL DEFAULT 0.0,
+    scroll_steps INTEGER NOT NULL DEFAULT 0,
+    scroll_up INTEGER NOT NULL DEFAULT 0,
+    scroll_down INTEGER NOT NULL DEFAULT 0,
+    scroll_left INTEGER NOT NULL DEFAULT 0,
+    scroll_right INTEGER NOT NULL DEFAULT 0,
+    scroll_vertical_precise REAL NOT NULL DEFAULT 0.0,
+    scroll_horizontal_precise REAL NOT NULL DEFAULT 0.0,
+    left_clicks INTEGER NOT NULL DEFAULT 0,
+    right_clicks INTEGER NOT NULL DEFAULT 0,
+    middle_clicks INTEGER NOT NULL DEFAULT 0,
+    extra_clicks INTEGER NOT NULL DEFAULT 0,
+    double_clicks INTEGER NOT NULL DEFAULT 0,
+    triple_clicks INTEGER NOT NULL DEFAULT 0,
+    drags INTEGER NOT NULL DEFAULT 0,
+    drag_distance_in REAL NOT NULL DEFAULT 0.0,
+    typing_keys INTEGER NOT NULL DEFAULT 0,
+    typing_seconds REAL NOT NULL DEFAULT 0.0,
+    peak_kpm_1m REAL NOT NULL DEFAULT 0.0,
+    peak_kpm_5m REAL NOT NULL DEFAULT 0.0
+);
+
+CREATE TABLE metrics_daily (
+    bucket_start DATETIME PRIMARY KEY,
+    samples INTEGER NOT NULL DEFAULT 0,
+    keypresses INTEGER NOT NULL DEFAULT 0,
+    mouse_clicks INTEGER NOT NULL DEFAULT 0,
+    mouse_distance_in REAL NOT NULL DEFAULT 0.0,
+    mouse_distance_mi REAL NOT NULL DEFAULT 0.0,
+    scroll_steps INTEGER NOT NULL DEFAULT 0,
+    scroll_up INTEGER NOT NULL DEFAULT 0,
+    scroll_down INTEGER NOT NULL DEFAULT 0,
+    scroll_left INTEGER NOT NULL DEFAULT 0,
+    scroll_right INTEGER NOT NULL DEFAULT 0,
+    scroll_vertical_precise REAL NOT NULL DEFAULT 0.0,
+    scroll_horizontal_precise REAL NOT NULL DEFAULT 0.0,
+    left_clicks INTEGER NOT NULL DEFAULT 0,
+    right_clicks INTEGER NOT NULL DEFAULT 0,
+    middle_clicks INTEGER NOT NULL DEFAULT 0,
+    extra_clicks INTEGER NOT NULL DEFAULT 0,
+    double_clicks INTEGER NOT NULL DEFAULT 0,
+    triple_clicks INTEGER NOT NULL DEFAULT 0,
+    drags INTEGER NOT NULL DEFAULT 0,
+    drag_distance_in REAL NOT NULL DEFAULT 0.0,
+    typing_keys INTEGER NOT NULL DEFAULT 0,
+    typing_seconds REAL NOT NULL DEFAULT 0.0,
+    peak_kpm_1m REAL NOT NULL DEFAULT 0.0,
+    peak_kpm_5m REAL NOT NULL DEFAULT 0.0
+);
+
+CREATE TABLE key_counts_hourly (
+    bucket_start DATETIME NOT NULL,
+    keycode TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY (bucket_start, keycode)
+);
+
+CREATE TABLE key_counts_daily (
+    bucket_start DATETIME NOT NULL,
+    keycode TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY (bucket_start, keycode)
+);
+
+INSERT INTO metrics_hourly (
+    bucket_start, samples, keypresses, mouse_clicks, mouse_distance_in,
+    mouse_distance_mi, scroll_steps, scroll_up, scroll_down,
+    scroll_left, scroll_right, scroll_vertical_precise,
+    scroll_horizontal_precise, left_clicks, right_clicks, middle_clicks,
+    extra_clicks, double_clicks, triple_clicks, drags, drag_distance_in,
+    typing_keys, typing_seconds, peak_kpm_1m, peak_kpm_5m
+)
+SELECT
+    strftime('%Y-%m-%d %H:00:00', timestamp),
+    COUNT(*),
+    COALESCE(SUM(keypresses), 0),
+    COALESCE(SUM(mouse_clicks), 0),
+    COALESCE(SUM(mouse_distance_in), 0),
+    COALESCE(SUM(mouse_distance_mi), 0),
+    COALESCE(SUM(scroll_steps), 0),
+    COALESCE(SUM(scroll_up), 0),
+    COALESCE(SUM(scroll_down), 0),
+    COALESCE(SUM(scroll_left), 0),
+    COALESCE(SUM(scroll_right), 0),
+    COALESCE(SUM(scroll_vertical_precise), 0),
+    COALESCE(SUM(scroll_horizontal_precise), 0),
+    COALESCE(SUM(left_clicks), 0),
+    COALESCE(SUM(right_clicks), 0),
+    COALESCE(SUM(middle_clicks), 0),
+    COALESCE(SUM(extra_clicks), 0),
+    COALESCE(SUM(double_clicks), 0),
+    COALESCE(SUM(triple_clicks), 0),
+    COALESCE(SUM(drags), 0),
+    COALESCE(SUM(drag_distance_in), 0),
+    COALESCE(SUM(typing_keys), 0),
+    COALESCE(SUM(typing_seconds), 0),
+    MAX(peak_kpm_1m),
+    MAX(peak_kpm_5m)
+FROM metrics
+WHERE timestamp IS NOT NULL
+GROUP BY 1;
+
+INSERT INTO metrics_daily (
+    bucket_start, samples, keypresses, mouse_clicks, mouse_distance_in,
+    mouse_distance_mi, scroll_steps, scroll_up, scroll_down,
+    scroll_left, scroll_right, scroll_vertical_precise,
+    scroll_horizontal_precise, left_clicks, right_clicks, middle_clicks,
+    extra_clicks, double_clicks, triple_clicks, drags, drag_distance_in,
+    typing_keys, typing_seconds, peak_kpm_1m, peak_kpm_5m
+)
+SELECT
+    strftime('%Y-%m-%d 00:00:00', timestamp),
+    COUNT(*),
+    COALESCE(SUM(keypresses), 0),
+    COALESCE(SUM(mouse_clicks), 0),
+    COALESCE(SUM(mouse_distance_in), 0),
+    COALESCE(SUM(mouse_distance_mi), 0),
+    COALESCE(SUM(scroll_steps), 0),
+    COALESCE(SUM(scroll_up), 0),
+    COALESCE(SUM(scroll_down), 0),
+    COALESCE(SUM(scroll_left), 0),
+    COALESCE(SUM(scroll_right), 0),
+    COALESCE(SUM(scroll_vertical_precise), 0),
+    COALESCE(SUM(scroll_horizontal_precise), 0),
+    COALESCE(SUM(left_clicks), 0),
+    COALESCE(SUM(right_clicks), 0),
+    COALESCE(SUM(middle_clicks), 0),
+    COALESCE(SUM(extra_clicks), 0),
+    COALESCE(SUM(double_clicks), 0),
+    COALESCE(SUM(triple_clicks), 0),
+    COALESCE(SUM(drags), 0),
+    COALESCE(SUM(drag_distance_in), 0),
+    COALESCE(SUM(typing_keys), 0),
+    COALESCE(SUM(typing_seconds), 0),
+    MAX(peak_kpm_1m),
+    MAX(peak_kpm_5m)
+FROM metrics
+WHERE timestamp IS NOT NULL
+GROUP BY 1;
+
+INSERT INTO key_counts_hourly (bucket_start, keycode, count)
+SELECT strftime('%Y-%m-%d %H:00:00', m.timestamp), k.keycode, SUM(k.count)
+FROM key_counts k
+JOIN metrics m ON m.id = k.metrics_id
+WHERE m.timestamp IS NOT NULL
+GROUP BY 1, 2;
+
+INSERT INTO key_counts_daily (bucket_start, keycode, count)
+SELECT strftime('%Y-%m-%d 00:00:00', m.timestamp), k.keycode, SUM(k.count)
+FROM key_counts k
+JOIN metrics m ON m.id = k.metrics_id
+WHERE m.timestamp IS NOT NULL
+GROUP BY 1, 2;
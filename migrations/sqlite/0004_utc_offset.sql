@@ -0,0 +1,6 @@
+-- The UTC offset of the storage timezone (database.timezone, or the
+-- system's if that isn't set), in seconds, when each interval was saved, so
+-- its local time survives a change of timezone. NULL for intervals saved
+-- before it was recorded.
+
+ALTER TABLE metrics ADD COLUMN utc_offset_seconds INTEGER;
@@ -0,0 +1,17 @@
+-- The client-generated id of each interval and when it started; it ends at
+-- its timestamp. NULL for intervals saved before they were recorded.
+
+ALTER TABLE metrics ADD COLUMN interval_id TEXT;
+ALTER TABLE metrics ADD COLUMN interval_start DATETIME;
+
+CREATE UNIQUE INDEX idx_metrics_interval_id ON metrics (interval_id);
+
+-- Intervals already waiting in the sync outbox get an id too. Their span
+-- isn't known, so it's taken to be the moment they were saved.
+UPDATE sync_outbox SET payload = json_set(
+    payload,
+    '$.id', lower(hex(randomblob(16))),
+    '$.start', strftime('%Y-%m-%dT%H:%M:%SZ', created_at),
+    '$.end', strftime('%Y-%m-%dT%H:%M:%SZ', created_at)
+)
+WHERE json_extract(payload, '$.id') IS NULL;
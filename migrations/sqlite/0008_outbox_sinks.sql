@@ -0,0 +1,7 @@
+-- Each remote sink has its own queue in the outbox, so an interval saved
+-- while several are configured gets a row for each. Rows queued before
+-- there were sinks were all bound for Supabase.
+
+ALTER TABLE sync_outbox ADD COLUMN sink TEXT NOT NULL DEFAULT 'supabase';
+
+CREATE INDEX idx_sync_outbox_sink ON sync_outbox (sink, id);
@@ -0,0 +1,11 @@
+-- Active minutes join the running totals, so reading the totals doesn't
+-- sum every session ever saved. Seeded from the sessions saved so far.
+
+ALTER TABLE metrics_totals ADD COLUMN active_minutes REAL NOT NULL DEFAULT 0.0;
+
+UPDATE metrics_totals
+SET active_minutes = (
+    SELECT COALESCE(SUM(julianday(end_time) - julianday(start_time)), 0.0) * 1440.0
+    FROM activity_sessions
+)
+WHERE id = 1;
@@ -0,0 +1,7 @@
+-- A sink that keeps answering but won't acknowledge an interval would have
+-- it sent again forever. Rejections are counted apart from failed requests,
+-- and past a limit the interval is dead-lettered: kept in the outbox with
+-- its last error, but no longer sent.
+
+ALTER TABLE sync_outbox ADD COLUMN rejections INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE sync_outbox ADD COLUMN dead_lettered_at DATETIME;
@@ -0,0 +1,18 @@
+-- Syncing to Supabase can be turned off and on again, and intervals saved
+-- while it's off aren't queued. Each stretch of history that wasn't queued
+-- is a gap: the history before sync first started, which has no start, and
+-- any time it was off since, which has no end while it still is. The
+-- backfill uploads the closed gaps, and has got as far as backfilled_until
+-- in each.
+
+CREATE TABLE sync_gaps (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at DATETIME,
+    ended_at DATETIME,
+    backfilled_until DATETIME
+);
+
+INSERT INTO sync_gaps (ended_at, backfilled_until)
+SELECT started_at, backfilled_until FROM sync_state WHERE started_at IS NOT NULL;
+
+DROP TABLE sync_state;
@@ -0,0 +1,94 @@
+use chrono::{DateTime, Duration, Utc};
+
+/// A stretch of time with no gap between inputs longer than the idle
+/// timeout. `end` is the time of the last input in it.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+/// Splits the input stream into active sessions.
+pub struct ActivityTracker {
+    idle_timeout: Duration,
+    current: Option<Session>,
+    finished: Vec<Session>,
+}
+
+impl ActivityTracker {
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            idle_timeout,
+            current: None,
+            finished: Vec::new(),
+        }
+    }
+
+    /// Notes an input at `time`, starting a new session if the user had
+    /// been idle.
+    pub fn record(&mut self, time: DateTime<Utc>) {
+        match self.current.as_mut() {
+            Some(session) if time - session.end <= self.idle_timeout => {
+                session.end = session.end.max(time);
+            }
+            _ => {
+                self.finish_current();
+                self.current = Some(Session { start: time, end: time });
+            }
+        }
+    }
+
+    /// Ends the current session if nothing has happened since the idle
+    /// timeout.
+    pub fn check_idle(&mut self, now: DateTime<Utc>) {
+        if let Some(session) = &self.current {
+            if now - session.end > self.idle_timeout {
+                self.finish_current();
+            }
+        }
+    }
+
+    /// Ends the current session where its last input was, as when the app
+    /// quits.
+    pub fn close(&mut self) {
+        self.finish_current();
+    }
+
+    /// Sessions that ended since the last call, followed by the one still
+    /// in progress, if any.
+    pub fn take_sessions(&mut self) -> Vec<Session> {
+        let mut sessions = std::mem::take(&mut self.finished);
+        sessions.extend(self.current.clone());
+        sessions
+    }
+
+    fn finish_current(&mut self) {
+        if let Some(session) = self.current.take() {
+            self.finished.push(session);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_772_000_000 + seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn closing_ends_the_session_in_progress() {
+        let mut tracker = ActivityTracker::new(Duration::seconds(60));
+        tracker.record(at(0));
+        tracker.record(at(20));
+        assert_eq!(tracker.take_sessions().len(), 1);
+
+        tracker.record(at(40));
+        tracker.close();
+        let sessions = tracker.take_sessions();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!((sessions[0].start, sessions[0].end), (at(0), at(40)));
+        assert!(tracker.take_sessions().is_empty());
+    }
+}
@@ -2,33 +2,63 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use crate::{
-    db::Database,
+    activity::ActivityTracker,
+    breaks::BreakTracker,
+    config::Config,
+    db::{self, Storage},
+    device,
+    journal::Journal,
     menubar::MenuBar,
-    metrics::{Metrics, TotalMetrics},
+    metrics::{PendingMetrics, TotalMetrics},
     monitor::get_monitors,
     monitor::Monitor,
 };
 
 pub struct AppState {
-    pub metrics: Mutex<Metrics>,
+    pub metrics: PendingMetrics,
+    pub journal: Journal,
     pub total_metrics: Mutex<TotalMetrics>,
     pub monitors: Mutex<Vec<Monitor>>,
-    pub db: Arc<Database>,
+    pub activity: Mutex<ActivityTracker>,
+    pub breaks: Mutex<BreakTracker>,
+    pub db: Arc<dyn Storage>,
+    /// The id this device's intervals are sent to every sink under.
+    pub device_id: String,
     pub menu_bar: Arc<Mutex<MenuBar>>,
 }
 
 impl AppState {
-    pub async fn initialize() -> anyhow::Result<Arc<Self>> {
-        let db = Arc::new(Database::new().await?);
+    pub async fn initialize(config: &Config) -> anyhow::Result<Arc<Self>> {
+        let db = db::open(&config.database).await?;
+
+        let metrics = PendingMetrics::default();
+        let journal = Journal::open()?;
+        if let Some(unsaved) = journal.load()? {
+            // The last run may have saved it and died before it could
+            // update the journal.
+            if db.has_interval(unsaved.id).await? {
+                log::info!("Discarding metrics journal for interval {}, which was already saved", unsaved.id);
+            } else {
+                log::info!("Recovered {} unsaved keypresses from the last run", unsaved.metrics.keypresses);
+                metrics.restore(&unsaved);
+            }
+        }
+
         let total_metrics = db.get_total_metrics().await?;
+        let device_id = device::get_or_create_device_id()?;
         let menu_bar = MenuBar::new()?;
         let monitors = get_monitors()?;
+        let idle_timeout = chrono::Duration::seconds(config.activity.idle_timeout_secs as i64);
 
         Ok(Arc::new(Self {
-            metrics: Mutex::new(Metrics::default()),
+            metrics,
+            journal,
             total_metrics: Mutex::new(total_metrics),
             monitors: Mutex::new(monitors),
+            activity: Mutex::new(ActivityTracker::new(idle_timeout)),
+            breaks: Mutex::new(BreakTracker::new(&config.breaks)),
             db,
+            device_id,
             menu_bar: Arc::new(Mutex::new(menu_bar)),
         }))
     }
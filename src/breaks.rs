@@ -0,0 +1,203 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::config::BreakConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BreakEvent {
+    /// The user has been active for `active_minutes` without a break.
+    Due { active_minutes: f64 },
+    /// A break long enough to count was taken after a reminder.
+    Taken,
+}
+
+/// Watches for continuous activity without a long enough gap and decides
+/// when to remind the user to take a break.
+pub struct BreakTracker {
+    work: Duration,
+    gap: Duration,
+    snooze: Duration,
+    // Start of the current stretch of activity and the last input in it.
+    run_start: Option<DateTime<Utc>>,
+    last_input: Option<DateTime<Utc>>,
+    next_due: Option<DateTime<Utc>>,
+    reminding: bool,
+    took_break: bool,
+}
+
+impl BreakTracker {
+    pub fn new(config: &BreakConfig) -> Self {
+        Self {
+            work: Duration::minutes(config.work_minutes as i64),
+            gap: Duration::minutes(config.break_minutes as i64),
+            snooze: Duration::minutes(config.snooze_minutes as i64),
+            run_start: None,
+            last_input: None,
+            next_due: None,
+            reminding: false,
+            took_break: false,
+        }
+    }
+
+    pub fn record(&mut self, time: DateTime<Utc>) {
+        let rested = match self.last_input {
+            Some(last) => time - last >= self.gap,
+            None => true,
+        };
+        if rested {
+            self.start_run(time);
+        }
+        self.last_input = Some(self.last_input.map_or(time, |last| last.max(time)));
+    }
+
+    /// What, if anything, changed by `now`. Call this periodically.
+    pub fn check(&mut self, now: DateTime<Utc>) -> Option<BreakEvent> {
+        // The user may still be on their break, with no input yet to end it.
+        if let Some(last) = self.last_input {
+            if self.run_start.is_some() && now - last >= self.gap {
+                self.run_start = None;
+                self.next_due = None;
+                self.took_break |= self.reminding;
+                self.reminding = false;
+            }
+        }
+
+        if std::mem::take(&mut self.took_break) {
+            return Some(BreakEvent::Taken);
+        }
+
+        let (Some(run_start), Some(next_due)) = (self.run_start, self.next_due) else {
+            return None;
+        };
+        if self.reminding || now < next_due {
+            return None;
+        }
+        self.reminding = true;
+        Some(BreakEvent::Due {
+            active_minutes: (now - run_start).num_seconds() as f64 / 60.0,
+        })
+    }
+
+    /// Puts the current reminder off for the snooze duration.
+    pub fn snooze(&mut self, now: DateTime<Utc>) {
+        self.reminding = false;
+        self.next_due = Some(now + self.snooze);
+    }
+
+    /// Dismisses the current reminder. The next one comes after another
+    /// full work period.
+    pub fn skip(&mut self, now: DateTime<Utc>) {
+        self.reminding = false;
+        self.next_due = Some(now + self.work);
+    }
+
+    fn start_run(&mut self, time: DateTime<Utc>) {
+        self.took_break |= self.reminding;
+        self.reminding = false;
+        self.run_start = Some(time);
+        self.next_due = Some(time + self.work);
+    }
+}
+
+/// How a break reminder ended, as stored in the database.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BreakOutcome {
+    Pending,
+    Taken,
+    Snoozed,
+    Skipped,
+}
+
+impl BreakOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BreakOutcome::Pending => "pending",
+            BreakOutcome::Taken => "taken",
+            BreakOutcome::Snoozed => "snoozed",
+            BreakOutcome::Skipped => "skipped",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn minute(m: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 2, 9, 0, 0).unwrap() + Duration::minutes(m)
+    }
+
+    // 50 minutes of work, 5 minute breaks, 10 minute snoozes.
+    fn tracker() -> BreakTracker {
+        BreakTracker::new(&BreakConfig::default())
+    }
+
+    // Input once a minute from `from` to `to`, inclusive.
+    fn active(tracker: &mut BreakTracker, from: i64, to: i64) {
+        for m in from..=to {
+            tracker.record(minute(m));
+        }
+    }
+
+    #[test]
+    fn reminds_once_after_work_period() {
+        let mut breaks = tracker();
+        active(&mut breaks, 0, 49);
+        assert_eq!(breaks.check(minute(49)), None);
+
+        breaks.record(minute(50));
+        assert_eq!(breaks.check(minute(50)), Some(BreakEvent::Due { active_minutes: 50.0 }));
+        active(&mut breaks, 51, 60);
+        assert_eq!(breaks.check(minute(60)), None);
+    }
+
+    #[test]
+    fn long_enough_gap_starts_over() {
+        let mut breaks = tracker();
+        active(&mut breaks, 0, 30);
+        // Four idle minutes aren't a break.
+        active(&mut breaks, 34, 40);
+        // Five are.
+        active(&mut breaks, 45, 94);
+        assert_eq!(breaks.check(minute(94)), None);
+        breaks.record(minute(95));
+        assert_eq!(breaks.check(minute(95)), Some(BreakEvent::Due { active_minutes: 50.0 }));
+    }
+
+    #[test]
+    fn break_after_reminder_is_taken() {
+        let mut breaks = tracker();
+        active(&mut breaks, 0, 50);
+        assert!(matches!(breaks.check(minute(50)), Some(BreakEvent::Due { .. })));
+
+        // Still away, with no input yet to end the break.
+        assert_eq!(breaks.check(minute(54)), None);
+        assert_eq!(breaks.check(minute(55)), Some(BreakEvent::Taken));
+        assert_eq!(breaks.check(minute(56)), None);
+
+        // Coming back starts a fresh work period.
+        active(&mut breaks, 60, 109);
+        assert_eq!(breaks.check(minute(109)), None);
+        breaks.record(minute(110));
+        assert_eq!(breaks.check(minute(110)), Some(BreakEvent::Due { active_minutes: 50.0 }));
+    }
+
+    #[test]
+    fn snooze_and_skip_postpone_the_reminder() {
+        let mut breaks = tracker();
+        active(&mut breaks, 0, 50);
+        assert!(matches!(breaks.check(minute(50)), Some(BreakEvent::Due { .. })));
+
+        breaks.snooze(minute(50));
+        active(&mut breaks, 51, 59);
+        assert_eq!(breaks.check(minute(59)), None);
+        breaks.record(minute(60));
+        assert_eq!(breaks.check(minute(60)), Some(BreakEvent::Due { active_minutes: 60.0 }));
+
+        breaks.skip(minute(60));
+        active(&mut breaks, 61, 109);
+        assert_eq!(breaks.check(minute(109)), None);
+        breaks.record(minute(110));
+        assert_eq!(breaks.check(minute(110)), Some(BreakEvent::Due { active_minutes: 110.0 }));
+    }
+}
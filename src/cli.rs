@@ -0,0 +1,415 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use clap::{Parser, Subcommand};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::db::{self, BreakCompliance, Bucket, Granularity, Period, Storage, SyncGap};
+use crate::device;
+use crate::heatmap::{self, Layout, Scale};
+use crate::metrics::{Interval, Metrics, TypingSpeed};
+use crate::sink::RemoteSink;
+use crate::supabase::SupabaseClient;
+use crate::sync::CircuitBreaker;
+use crate::tasks::sync::sync_pending;
+
+#[derive(Parser)]
+#[command(name = "kweeb-logger", version, about = "Counts keyboard and mouse activity")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Render a keyboard heatmap of key presses as SVG
+    Heatmap {
+        /// Start of the range, as YYYY-MM-DD (local midnight) or RFC 3339.
+        /// Defaults to the beginning of recorded history.
+        #[arg(long, value_parser = parse_time)]
+        from: Option<TimeArg>,
+        /// End of the range (exclusive). Defaults to now.
+        #[arg(long, value_parser = parse_time)]
+        to: Option<TimeArg>,
+        #[arg(long, value_enum, default_value_t = Layout::Ansi)]
+        layout: Layout,
+        #[arg(long, value_enum, default_value_t = Scale::Linear)]
+        scale: Scale,
+        /// Where to write the SVG
+        #[arg(short, long, default_value = "heatmap.svg")]
+        output: PathBuf,
+    },
+    /// Print active time, typing speed and break compliance for each day
+    Report {
+        /// Start of the range, as YYYY-MM-DD (local midnight) or RFC 3339.
+        /// Defaults to the beginning of recorded history.
+        #[arg(long, value_parser = parse_time)]
+        from: Option<TimeArg>,
+        /// End of the range (exclusive). Defaults to now.
+        #[arg(long, value_parser = parse_time)]
+        to: Option<TimeArg>,
+    },
+    /// Print metrics over a range, summed per minute, hour, day, week or
+    /// month
+    Series {
+        /// Start of the range, as YYYY-MM-DD (local midnight) or RFC 3339.
+        /// Defaults to the start of today.
+        #[arg(long, value_parser = parse_time)]
+        from: Option<TimeArg>,
+        /// End of the range (exclusive). Defaults to now.
+        #[arg(long, value_parser = parse_time)]
+        to: Option<TimeArg>,
+        #[arg(long, value_enum, default_value_t = Bucket::Hour)]
+        bucket: Bucket,
+    },
+    /// Print totals for today, this week and this month
+    Totals,
+    /// Recompute all-time totals from history and report any drift from
+    /// the stored running totals
+    Verify,
+    /// Sync with Supabase
+    Sync {
+        #[command(subcommand)]
+        command: SyncCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SyncCommand {
+    /// Upload the history saved before syncing was set up. Progress is
+    /// saved a day at a time, so running it again resumes where it stopped
+    Backfill,
+}
+
+/// A `--from` or `--to` value. A bare date means midnight in the storage
+/// timezone, which isn't known until the database is open.
+#[derive(Debug, Clone, Copy)]
+pub enum TimeArg {
+    Instant(DateTime<Utc>),
+    Date(NaiveDate),
+}
+
+impl TimeArg {
+    fn resolve(self, timezone: Tz) -> DateTime<Utc> {
+        match self {
+            TimeArg::Instant(time) => time,
+            TimeArg::Date(date) => db::local_midnight(date, timezone),
+        }
+    }
+}
+
+pub async fn run(command: Command, config: &Config) -> Result<()> {
+    let db = db::open(&config.database).await?;
+    let timezone = db.timezone();
+
+    match command {
+        Command::Heatmap { from, to, layout, scale, output } => {
+            let start = from.map_or(DateTime::UNIX_EPOCH, |from| from.resolve(timezone));
+            let end = to.map(|to| to.resolve(timezone));
+            let svg = heatmap::render_from_db(db.as_ref(), start, end, layout, scale).await?;
+            std::fs::write(&output, svg)
+                .with_context(|| format!("Failed to write {}", output.display()))?;
+            println!("Wrote heatmap to {}", output.display());
+        }
+        Command::Report { from, to } => {
+            let start = from.map_or(DateTime::UNIX_EPOCH, |from| from.resolve(timezone));
+            let end = to.map(|to| to.resolve(timezone));
+            print_daily_report(db.as_ref(), start, end).await?;
+        }
+        Command::Series { from, to, bucket } => {
+            let end = to.map(|to| to.resolve(timezone));
+            let start = from.map_or_else(
+                || Period::Today.start(end.unwrap_or_else(Utc::now), timezone),
+                |from| from.resolve(timezone),
+            );
+            print_series(db.as_ref(), start, end, bucket).await?;
+        }
+        Command::Totals => {
+            print_metrics_header("period");
+            let periods = [
+                ("today", Period::Today),
+                ("this week", Period::ThisWeek),
+                ("this month", Period::ThisMonth),
+            ];
+            for (label, period) in periods {
+                print_metrics_row(label, &db.get_period_totals(period).await?);
+            }
+        }
+        Command::Verify => {
+            let drift = db.verify_totals().await?;
+            if drift.is_empty() {
+                println!("Totals match");
+                return Ok(());
+            }
+            println!("{:<26}  {:>16}  {:>16}", "column", "stored", "recomputed");
+            for column in &drift {
+                println!("{:<26}  {:>16.3}  {:>16.3}", column.column, column.stored, column.recomputed);
+            }
+            anyhow::bail!("{} totals drifted from history", drift.len());
+        }
+        Command::Sync { command: SyncCommand::Backfill } => {
+            if !config.has_supabase_config() {
+                anyhow::bail!("Supabase isn't configured");
+            }
+            let device_id = device::get_or_create_device_id()?;
+            let supabase = SupabaseClient::new(
+                config.supabase.url.as_ref().unwrap(),
+                config.supabase.api_key.as_ref().unwrap(),
+                device_id.clone(),
+            )?;
+            let max_batch_size = config.sync.max_batch_size.max(1);
+            backfill(db.as_ref(), &supabase, &device_id, max_batch_size).await?;
+        }
+    }
+
+    Ok(())
+}
+
+// Rollup buckets and intervals saved without an id are named by where they
+// came from, so queueing one again after an interruption gives it the same
+// id.
+const BACKFILL_NAMESPACE: Uuid = Uuid::from_u128(0x5f0c_8f5e_3b0a_4c1e_9d2b_6e7a_1c4f_8b30);
+
+// Queues the history saved while sync to Supabase was off, a gap at a time
+// and a UTC day at a time, from the finest granularity still stored for that
+// day, and uploads each day through the sync outbox. Raw intervals go up
+// with the id and start they were saved with. A rollup bucket that a gap
+// starts or ends partway through was partly queued already, so the backfill
+// leaves it out, and only picks up after it if raw intervals are still kept
+// there.
+async fn backfill(db: &dyn Storage, supabase: &SupabaseClient, device_id: &str, max_batch_size: u32) -> Result<()> {
+    let gaps = db.sync_gaps().await?;
+    if gaps.is_empty() {
+        anyhow::bail!("Sync to Supabase hasn't started yet, run kweeb-logger with Supabase configured first");
+    }
+
+    let mut breaker = CircuitBreaker::new();
+    let mut backfilled = false;
+    for gap in &gaps {
+        backfilled |= backfill_gap(db, supabase, device_id, gap, max_batch_size, &mut breaker).await?;
+    }
+    if backfilled {
+        println!("Backfilled the history saved while sync was off");
+    } else {
+        println!("Nothing to backfill");
+    }
+    Ok(())
+}
+
+// Backfills what's left of `gap`, and returns whether there was any of it.
+async fn backfill_gap(
+    db: &dyn Storage,
+    supabase: &SupabaseClient,
+    device_id: &str,
+    gap: &SyncGap,
+    max_batch_size: u32,
+    breaker: &mut CircuitBreaker,
+) -> Result<bool> {
+    let Some(end) = gap.end else {
+        if let Some(start) = gap.start {
+            println!(
+                "Sync to Supabase has been off since {}, run the backfill again once it's back on",
+                start.to_rfc3339()
+            );
+        }
+        return Ok(false);
+    };
+    let mut start = match gap.backfilled_until.or(gap.start) {
+        Some(start) => start,
+        None => match db.oldest(Granularity::Daily).await? {
+            Some(oldest) => Granularity::Daily.bucket_start(oldest),
+            None => end,
+        },
+    };
+    if start >= end {
+        return Ok(false);
+    }
+
+    while start < end {
+        let granularity = db.finest_granularity(start).await?;
+        let span = match granularity {
+            Granularity::Raw => Duration::zero(),
+            Granularity::Hourly => Duration::hours(1),
+            Granularity::Daily => Duration::days(1),
+        };
+        if granularity != Granularity::Raw && granularity.bucket_start(start) < start {
+            start = (granularity.bucket_start(start) + span).min(end);
+            continue;
+        }
+        let day_end = (Granularity::Daily.bucket_start(start) + Duration::days(1)).min(end);
+        let until = match granularity {
+            Granularity::Raw => day_end,
+            Granularity::Hourly | Granularity::Daily => day_end.min(granularity.bucket_start(end)),
+        };
+        if until <= start {
+            println!(
+                "History from {} to {} is only kept in {:?} rollups that were partly synced already, \
+                 so it isn't backfilled",
+                start.to_rfc3339(),
+                end.to_rfc3339(),
+                granularity
+            );
+            db.queue_backfill(supabase.name(), &[], gap.id, end).await?;
+            break;
+        }
+
+        let backfill_id = |time: DateTime<Utc>| {
+            let name = format!("{}/{:?}/{}", device_id, granularity, time.to_rfc3339());
+            Uuid::new_v3(&BACKFILL_NAMESPACE, name.as_bytes())
+        };
+        let mut intervals: Vec<Interval> = match granularity {
+            Granularity::Raw => db
+                .get_saved_intervals(start, until)
+                .await?
+                .into_iter()
+                .map(|saved| Interval {
+                    id: saved.id.unwrap_or_else(|| backfill_id(saved.end)),
+                    start: saved.start.unwrap_or(saved.end),
+                    end: saved.end,
+                    metrics: saved.metrics,
+                })
+                .collect(),
+            Granularity::Hourly | Granularity::Daily => db
+                .get_metric_rows(start, until, granularity)
+                .await?
+                .into_iter()
+                .map(|(time, metrics)| Interval {
+                    id: backfill_id(time),
+                    start: time,
+                    end: time + span,
+                    metrics,
+                })
+                .collect(),
+        };
+        intervals.retain(|interval| !interval.metrics.is_empty());
+
+        db.queue_backfill(supabase.name(), &intervals, gap.id, until).await?;
+        drain_outbox(db, supabase, max_batch_size, breaker).await?;
+        println!("{}  {:>6} {:?} rows", start.date_naive(), intervals.len(), granularity);
+        start = until;
+    }
+
+    Ok(true)
+}
+
+// Uploads what's queued for `sink` until none of it is pending, leaving
+// what the sink keeps rejecting dead-lettered in the outbox.
+async fn drain_outbox(
+    db: &dyn Storage,
+    sink: &dyn RemoteSink,
+    max_batch_size: u32,
+    breaker: &mut CircuitBreaker,
+) -> Result<()> {
+    loop {
+        match sync_pending(db, sink, max_batch_size, breaker).await? {
+            Some(delay) => {
+                if breaker.is_open() {
+                    anyhow::bail!(
+                        "{} keeps failing, the queued history is uploaded once it's back, \
+                         or run the backfill again to wait for it",
+                        sink.name()
+                    );
+                }
+                tokio::time::sleep(delay).await;
+            }
+            None if db.pending_sync(sink.name(), 1).await?.is_empty() => return Ok(()),
+            None => {}
+        }
+    }
+}
+
+async fn print_daily_report(db: &dyn Storage, start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> Result<()> {
+    let mut days: BTreeMap<NaiveDate, (f64, TypingSpeed, BreakCompliance)> = BTreeMap::new();
+    for (date, typing) in db.get_typing_speed_by_day(start, end).await? {
+        days.entry(date).or_default().1 = typing;
+    }
+    let end = end.unwrap_or_else(Utc::now);
+    for day in db.get_active_minutes_by_day(start, end).await? {
+        days.entry(day.date).or_default().0 = day.active_minutes;
+    }
+    for (date, breaks) in db.get_break_compliance_by_day(start, end).await? {
+        days.entry(date).or_default().2 = breaks;
+    }
+
+    if days.is_empty() {
+        println!("No activity recorded");
+        return Ok(());
+    }
+
+    println!(
+        "{:<10}  {:>8}  {:>7}  {:>11}  {:>11}  {:>6}  {:>5}  {:>7}  {:>7}",
+        "day", "active", "avg wpm", "peak wpm 1m", "peak wpm 5m", "breaks", "taken", "snoozed", "skipped",
+    );
+    for (date, (active_minutes, typing, breaks)) in days {
+        let minutes = active_minutes.round() as i64;
+        println!(
+            "{:<10}  {:>4}h {:02}m  {:>7.0}  {:>11.0}  {:>11.0}  {:>6}  {:>5}  {:>7}  {:>7}",
+            date,
+            minutes / 60,
+            minutes % 60,
+            typing.avg_wpm(),
+            typing.peak_wpm_1m(),
+            typing.peak_wpm_5m(),
+            breaks.reminders,
+            breaks.taken,
+            breaks.snoozed,
+            breaks.skipped,
+        );
+    }
+
+    Ok(())
+}
+
+async fn print_series(db: &dyn Storage, start: DateTime<Utc>, end: Option<DateTime<Utc>>, bucket: Bucket) -> Result<()> {
+    let series = db.get_series(start, end, bucket).await?;
+    if series.is_empty() {
+        println!("No metrics recorded");
+        return Ok(());
+    }
+
+    let format = match bucket {
+        Bucket::Minute | Bucket::Hour => "%Y-%m-%d %H:%M",
+        Bucket::Day | Bucket::Week => "%Y-%m-%d",
+        Bucket::Month => "%Y-%m",
+    };
+    print_metrics_header("start");
+    for (bucket_start, metrics) in series {
+        let label = bucket_start.with_timezone(&db.timezone()).format(format).to_string();
+        print_metrics_row(&label, &metrics);
+    }
+
+    Ok(())
+}
+
+fn print_metrics_header(label: &str) {
+    println!(
+        "{:<16}  {:>10}  {:>8}  {:>10}  {:>8}  {:>7}",
+        label, "keys", "clicks", "mouse in", "scroll", "avg wpm",
+    );
+}
+
+fn print_metrics_row(label: &str, metrics: &Metrics) {
+    println!(
+        "{:<16}  {:>10}  {:>8}  {:>10.1}  {:>8}  {:>7.0}",
+        label,
+        metrics.keypresses,
+        metrics.mouse_clicks,
+        metrics.mouse_distance_in,
+        metrics.scroll_steps,
+        metrics.typing_wpm(),
+    );
+}
+
+fn parse_time(value: &str) -> Result<TimeArg, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(TimeArg::Instant(time.with_timezone(&Utc)));
+    }
+
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(TimeArg::Date)
+        .map_err(|_| format!("expected YYYY-MM-DD or an RFC 3339 time, got `{}`", value))
+}
@@ -0,0 +1,223 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::input::MouseButton;
+use crate::metrics::Metrics;
+use crate::monitor::{calculate_distance, calculate_multi_monitor_distance, Monitor};
+
+// Presses of the same button closer together than this, in time and in
+// space, continue a multi-click.
+const MULTI_CLICK_INTERVAL_MS: i64 = 500;
+const MULTI_CLICK_MAX_DISTANCE_PX: f64 = 4.0;
+
+// A press becomes a drag once the pointer has moved this far from where
+// the button went down.
+const DRAG_THRESHOLD_PX: f64 = 5.0;
+
+struct LastClick {
+    button: MouseButton,
+    time: DateTime<Utc>,
+    position: Option<(i32, i32)>,
+    count: u32,
+}
+
+struct Press {
+    button: MouseButton,
+    // Pointer offset from where the button went down, in pixels.
+    offset_x: i32,
+    offset_y: i32,
+    distance_in: f64,
+    dragging: bool,
+}
+
+/// Breaks clicks down by button and recognises double/triple clicks and
+/// drags from the press, move and release events around them.
+#[derive(Default)]
+pub struct ClickTracker {
+    // Where the pointer is. Sources that only report relative motion get a
+    // position relative to wherever they started.
+    position: Option<(i32, i32)>,
+    last_click: Option<LastClick>,
+    press: Option<Press>,
+}
+
+impl ClickTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn button_down(&mut self, button: MouseButton, time: DateTime<Utc>, metrics: &mut Metrics) {
+        let position = self.position;
+        metrics.mouse_clicks += 1;
+        match button {
+            MouseButton::Left => metrics.left_clicks += 1,
+            MouseButton::Right => metrics.right_clicks += 1,
+            MouseButton::Middle => metrics.middle_clicks += 1,
+            MouseButton::Other(_) => metrics.extra_clicks += 1,
+        }
+
+        let count = match &self.last_click {
+            Some(last) if last.button == button
+                && last.count < 3
+                && time - last.time <= Duration::milliseconds(MULTI_CLICK_INTERVAL_MS)
+                && within_multi_click_distance(last.position, position) => last.count + 1,
+            _ => 1,
+        };
+        match count {
+            2 => metrics.double_clicks += 1,
+            3 => metrics.triple_clicks += 1,
+            _ => {}
+        }
+        self.last_click = Some(LastClick { button, time, position, count });
+
+        if self.press.is_none() {
+            self.press = Some(Press {
+                button,
+                offset_x: 0,
+                offset_y: 0,
+                distance_in: 0.0,
+                dragging: false,
+            });
+        }
+    }
+
+    pub fn button_up(&mut self, button: MouseButton, metrics: &mut Metrics) {
+        if self.press.as_ref().map(|press| press.button) != Some(button) {
+            return;
+        }
+        if let Some(press) = self.press.take() {
+            if press.dragging {
+                metrics.drags += 1;
+                metrics.drag_distance_in += press.distance_in;
+            }
+        }
+    }
+
+    /// Follows the pointer to `to` in screen coordinates.
+    pub fn pointer_moved(&mut self, to: (i32, i32), monitors: &[Monitor]) {
+        let from = self.position.replace(to);
+        let (Some(from), Some(_)) = (from, self.press.as_ref()) else {
+            return;
+        };
+        let distance_in = calculate_multi_monitor_distance(from.0, from.1, to.0, to.1, monitors)
+            .unwrap_or(0.0);
+        self.track(to.0 - from.0, to.1 - from.1, distance_in);
+    }
+
+    /// Follows relative pointer motion, already converted to inches.
+    pub fn pointer_delta(&mut self, dx: i32, dy: i32, distance_in: f64) {
+        let (x, y) = self.position.unwrap_or((0, 0));
+        self.position = Some((x + dx, y + dy));
+        self.track(dx, dy, distance_in);
+    }
+
+    fn track(&mut self, dx: i32, dy: i32, distance_in: f64) {
+        let Some(press) = self.press.as_mut() else {
+            return;
+        };
+        press.offset_x += dx;
+        press.offset_y += dy;
+        press.distance_in += distance_in;
+        if !press.dragging
+            && calculate_distance(0, 0, press.offset_x, press.offset_y) > DRAG_THRESHOLD_PX
+        {
+            press.dragging = true;
+            // A drag never continues into a double click.
+            self.last_click = None;
+        }
+    }
+}
+
+fn within_multi_click_distance(a: Option<(i32, i32)>, b: Option<(i32, i32)>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => calculate_distance(a.0, a.1, b.0, b.1) <= MULTI_CLICK_MAX_DISTANCE_PX,
+        // Before the first pointer event there's nothing to compare.
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(ms: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 2, 9, 0, 0).unwrap() + Duration::milliseconds(ms)
+    }
+
+    fn click(tracker: &mut ClickTracker, button: MouseButton, ms: i64, metrics: &mut Metrics) {
+        tracker.button_down(button, at(ms), metrics);
+        tracker.button_up(button, metrics);
+    }
+
+    #[test]
+    fn clicks_in_quick_succession_are_double_and_triple() {
+        let mut tracker = ClickTracker::new();
+        let mut metrics = Metrics::default();
+        for ms in [0, 200, 400, 600] {
+            click(&mut tracker, MouseButton::Left, ms, &mut metrics);
+        }
+
+        assert_eq!(metrics.mouse_clicks, 4);
+        assert_eq!(metrics.left_clicks, 4);
+        assert_eq!(metrics.double_clicks, 1);
+        assert_eq!(metrics.triple_clicks, 1);
+        // A fourth click starts over rather than counting as a triple.
+        click(&mut tracker, MouseButton::Left, 800, &mut metrics);
+        assert_eq!(metrics.double_clicks, 2);
+        assert_eq!(metrics.triple_clicks, 1);
+    }
+
+    #[test]
+    fn multi_click_needs_same_button_within_time_and_distance() {
+        let mut tracker = ClickTracker::new();
+        let mut metrics = Metrics::default();
+        click(&mut tracker, MouseButton::Left, 0, &mut metrics);
+        click(&mut tracker, MouseButton::Left, MULTI_CLICK_INTERVAL_MS, &mut metrics);
+        assert_eq!(metrics.double_clicks, 1);
+
+        click(&mut tracker, MouseButton::Right, 2000, &mut metrics);
+        click(&mut tracker, MouseButton::Left, 2100, &mut metrics);
+        click(&mut tracker, MouseButton::Left, 2101 + MULTI_CLICK_INTERVAL_MS, &mut metrics);
+        assert_eq!(metrics.double_clicks, 1);
+
+        tracker.pointer_delta(0, 0, 0.0);
+        click(&mut tracker, MouseButton::Middle, 4000, &mut metrics);
+        tracker.pointer_delta(4, 0, 0.0);
+        click(&mut tracker, MouseButton::Middle, 4100, &mut metrics);
+        assert_eq!(metrics.double_clicks, 2);
+        tracker.pointer_delta(3, 3, 0.0);
+        click(&mut tracker, MouseButton::Middle, 4200, &mut metrics);
+        assert_eq!(metrics.triple_clicks, 0);
+
+        assert_eq!(metrics.right_clicks, 1);
+        assert_eq!(metrics.middle_clicks, 3);
+        assert_eq!(metrics.left_clicks, 4);
+    }
+
+    #[test]
+    fn press_becomes_drag_past_threshold() {
+        let mut tracker = ClickTracker::new();
+        let mut metrics = Metrics::default();
+
+        // Moving exactly the threshold is still a click.
+        tracker.button_down(MouseButton::Left, at(0), &mut metrics);
+        tracker.pointer_delta(3, 4, 0.05);
+        tracker.button_up(MouseButton::Left, &mut metrics);
+        assert_eq!(metrics.drags, 0);
+
+        tracker.button_down(MouseButton::Left, at(1000), &mut metrics);
+        tracker.pointer_delta(4, 0, 0.04);
+        tracker.pointer_delta(2, 0, 0.02);
+        // Releasing another button doesn't end it.
+        tracker.button_up(MouseButton::Right, &mut metrics);
+        tracker.pointer_delta(10, 0, 0.1);
+        tracker.button_up(MouseButton::Left, &mut metrics);
+        assert_eq!(metrics.drags, 1);
+        assert!((metrics.drag_distance_in - 0.16).abs() < 1e-9);
+
+        // A click right after a drag isn't a double click.
+        click(&mut tracker, MouseButton::Left, 1100, &mut metrics);
+        assert_eq!(metrics.double_clicks, 0);
+        assert_eq!(metrics.drags, 1);
+    }
+}
@@ -0,0 +1,1867 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, DurationRound, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use clap::ValueEnum;
+use directories::ProjectDirs;
+use sqlx::database::HasArguments;
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::{ColumnIndex, Database, Decode, Encode, Executor, IntoArguments, Pool, Row, Type};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::activity::Session;
+use crate::breaks::BreakOutcome;
+use crate::config::DBConfig;
+use crate::metrics::{Interval, Metrics, TotalMetrics, TypingSpeed};
+
+mod postgres;
+mod sqlite;
+#[cfg(test)]
+pub mod testing;
+
+pub use postgres::PostgresStorage;
+pub use sqlite::SqliteStorage;
+
+#[derive(Debug, Clone)]
+pub struct KeyCount {
+    pub key: String,
+    pub count: i64,
+}
+
+/// What happened to the break reminders on one day.
+#[derive(Debug, Clone, Default)]
+pub struct BreakCompliance {
+    pub reminders: i64,
+    pub taken: i64,
+    pub snoozed: i64,
+    pub skipped: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DailyActivity {
+    /// Calendar day in the storage timezone.
+    pub date: NaiveDate,
+    pub active_minutes: f64,
+}
+
+// Columns of `metrics` that the rollup tables sum. They also keep the
+// highest `peak_kpm_1m` and `peak_kpm_5m` of each bucket.
+const ROLLUP_SUM_COLUMNS: &[&str] = &[
+    "keypresses",
+    "mouse_clicks",
+    "mouse_distance_in",
+    "mouse_distance_mi",
+    "scroll_steps",
+    "scroll_up",
+    "scroll_down",
+    "scroll_left",
+    "scroll_right",
+    "scroll_vertical_precise",
+    "scroll_horizontal_precise",
+    "left_clicks",
+    "right_clicks",
+    "middle_clicks",
+    "extra_clicks",
+    "double_clicks",
+    "triple_clicks",
+    "drags",
+    "drag_distance_in",
+    "typing_keys",
+    "typing_seconds",
+];
+
+// The columns of `ROLLUP_SUM_COLUMNS` that hold fractional amounts. The
+// rest are counts.
+const REAL_SUM_COLUMNS: &[&str] = &[
+    "mouse_distance_in",
+    "mouse_distance_mi",
+    "scroll_vertical_precise",
+    "scroll_horizontal_precise",
+    "drag_distance_in",
+    "typing_seconds",
+];
+
+/// Which copy of the metrics a query reads: the raw intervals, or the
+/// hourly or daily rollups. Rollup buckets start on UTC hours and days.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Granularity {
+    Raw,
+    Hourly,
+    Daily,
+}
+
+impl Granularity {
+    /// The coarsest granularity that answers a query over the range
+    /// exactly, or `finest`, the finest one whose history still reaches
+    /// back to the start of the range. A range without an `end` runs up to
+    /// now, and nothing has been saved after that for its last bucket to
+    /// take in.
+    pub fn for_range(start: DateTime<Utc>, end: Option<DateTime<Utc>>, finest: Self) -> Self {
+        for granularity in [Granularity::Daily, Granularity::Hourly] {
+            let covers = |time: DateTime<Utc>| granularity.bucket_start(time) == time;
+            if granularity >= finest && covers(start) && end.is_none_or(covers) {
+                return granularity;
+            }
+        }
+        finest
+    }
+
+    /// The finest granularity with history from `start` on, given the
+    /// oldest row still stored at each granularity. Nothing is older than
+    /// the daily rollups.
+    pub fn finest_since(
+        start: DateTime<Utc>,
+        oldest_raw: Option<DateTime<Utc>>,
+        oldest_hourly: Option<DateTime<Utc>>,
+        oldest_daily: Option<DateTime<Utc>>,
+    ) -> Self {
+        let levels = [
+            (Granularity::Raw, oldest_raw, oldest_hourly),
+            (Granularity::Hourly, oldest_hourly, oldest_daily),
+        ];
+        for (granularity, oldest, oldest_coarser) in levels {
+            let Some(oldest) = oldest else {
+                continue;
+            };
+            // Rows are only ever deleted a whole UTC day at a time, and
+            // none were if the coarser rollup has nothing older.
+            let kept_from = Granularity::Daily.bucket_start(oldest);
+            let never_deleted = oldest_coarser.is_none_or(|coarser| coarser >= kept_from);
+            if start >= kept_from || never_deleted {
+                return granularity;
+            }
+        }
+        Granularity::Daily
+    }
+
+    pub fn bucket_start(self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let bucket = match self {
+            Granularity::Raw => return time,
+            Granularity::Hourly => Duration::hours(1),
+            Granularity::Daily => Duration::days(1),
+        };
+        time.duration_trunc(bucket).unwrap_or(time)
+    }
+
+    fn metrics_table(self) -> &'static str {
+        match self {
+            Granularity::Raw => "metrics",
+            Granularity::Hourly => "metrics_hourly",
+            Granularity::Daily => "metrics_daily",
+        }
+    }
+
+    fn key_counts_table(self) -> &'static str {
+        match self {
+            Granularity::Raw => "key_counts",
+            Granularity::Hourly => "key_counts_hourly",
+            Granularity::Daily => "key_counts_daily",
+        }
+    }
+
+    fn time_column(self) -> &'static str {
+        match self {
+            Granularity::Raw => "timestamp",
+            Granularity::Hourly | Granularity::Daily => "bucket_start",
+        }
+    }
+}
+
+/// The width of each point in a metric series. Buckets start on minutes,
+/// hours, days, weeks (from Monday) and months in the storage timezone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Bucket {
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl Bucket {
+    /// The start of the bucket `time` falls in, in `timezone`.
+    pub fn start_of(self, time: DateTime<Utc>, timezone: Tz) -> DateTime<Utc> {
+        let local = time.with_timezone(&timezone);
+        let date = local.date_naive();
+        match self {
+            Bucket::Minute | Bucket::Hour => {
+                let width = if self == Bucket::Minute { Duration::minutes(1) } else { Duration::hours(1) };
+                local.duration_trunc(width).map_or(time, |start| start.with_timezone(&Utc))
+            }
+            Bucket::Day => local_midnight(date, timezone),
+            Bucket::Week => {
+                local_midnight(date - Duration::days(date.weekday().num_days_from_monday() as i64), timezone)
+            }
+            Bucket::Month => local_midnight(date.with_day(1).unwrap_or(date), timezone),
+        }
+    }
+
+    // The coarsest rollups that fill buckets of this width. Local days
+    // don't line up with the UTC days of the daily rollups. In zones whose
+    // offset isn't a whole number of hours they don't line up with hourly
+    // ones either, and split at the UTC hour instead.
+    fn granularity(self) -> Granularity {
+        match self {
+            Bucket::Minute => Granularity::Raw,
+            _ => Granularity::Hourly,
+        }
+    }
+}
+
+/// A calendar period up to now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Today,
+    ThisWeek,
+    ThisMonth,
+}
+
+impl Period {
+    /// The start of the period `now` is in, in `timezone`.
+    pub fn start(self, now: DateTime<Utc>, timezone: Tz) -> DateTime<Utc> {
+        let bucket = match self {
+            Period::Today => Bucket::Day,
+            Period::ThisWeek => Bucket::Week,
+            Period::ThisMonth => Bucket::Month,
+        };
+        bucket.start_of(now, timezone)
+    }
+}
+
+// Adds the `metrics` row with id $2 to the row of `table` whose `key` is
+// $1: a rollup bucket or the totals row. `greatest` is the backend's
+// two-argument maximum.
+fn accumulate_sql(table: &str, key: &str, greatest: &str) -> String {
+    let columns = ROLLUP_SUM_COLUMNS.join(", ");
+    let updates: Vec<String> = ROLLUP_SUM_COLUMNS
+        .iter()
+        .map(|column| format!("{column} = {table}.{column} + excluded.{column}"))
+        .collect();
+    format!(
+        "INSERT INTO {table} ({key}, samples, {columns}, peak_kpm_1m, peak_kpm_5m) \
+         SELECT $1, 1, {columns}, peak_kpm_1m, peak_kpm_5m FROM metrics WHERE id = $2 \
+         ON CONFLICT ({key}) DO UPDATE SET \
+         samples = {table}.samples + 1, {updates}, \
+         peak_kpm_1m = {greatest}({table}.peak_kpm_1m, excluded.peak_kpm_1m), \
+         peak_kpm_5m = {greatest}({table}.peak_kpm_5m, excluded.peak_kpm_5m)",
+        updates = updates.join(", "),
+    )
+}
+
+fn key_counts_upsert_sql(granularity: Granularity) -> String {
+    let table = granularity.key_counts_table();
+    format!(
+        "INSERT INTO {table} (bucket_start, keycode, count) VALUES ($1, $2, $3) \
+         ON CONFLICT (bucket_start, keycode) DO UPDATE SET count = {table}.count + excluded.count"
+    )
+}
+
+// Key press totals in [$1, $2), most pressed first, at most $3 of them.
+// Postgres sums BIGINT into NUMERIC, hence the casts here and below.
+fn key_counts_sql(granularity: Granularity) -> String {
+    let table = granularity.key_counts_table();
+    match granularity {
+        Granularity::Raw => format!(
+            "SELECT k.keycode, CAST(SUM(k.count) AS BIGINT) AS total \
+             FROM {table} k JOIN metrics m ON m.id = k.metrics_id \
+             WHERE m.timestamp >= $1 AND m.timestamp < $2 \
+             GROUP BY k.keycode ORDER BY total DESC, k.keycode LIMIT $3"
+        ),
+        Granularity::Hourly | Granularity::Daily => format!(
+            "SELECT keycode, CAST(SUM(count) AS BIGINT) AS total \
+             FROM {table} \
+             WHERE bucket_start >= $1 AND bucket_start < $2 \
+             GROUP BY keycode ORDER BY total DESC, keycode LIMIT $3"
+        ),
+    }
+}
+
+// Every summed column and the peaks of each row in [$1, $2), after its
+// start, oldest first.
+fn metric_rows_sql(granularity: Granularity) -> String {
+    format!(
+        "SELECT {time}, {columns}, peak_kpm_1m, peak_kpm_5m \
+         FROM {table} WHERE {time} >= $1 AND {time} < $2 ORDER BY {time}",
+        table = granularity.metrics_table(),
+        time = granularity.time_column(),
+        columns = ROLLUP_SUM_COLUMNS.join(", "),
+    )
+}
+
+// The rows `metric_rows_sql` reads, summed into one, with the highest
+// peaks. Each column keeps its name and type.
+fn metric_sums_sql(granularity: Granularity) -> String {
+    let aggregates: Vec<String> = ROLLUP_SUM_COLUMNS
+        .iter()
+        .map(|&column| {
+            let column_type = if REAL_SUM_COLUMNS.contains(&column) { "DOUBLE PRECISION" } else { "BIGINT" };
+            format!("CAST(COALESCE(SUM({column}), 0) AS {column_type}) AS {column}")
+        })
+        .chain(["peak_kpm_1m", "peak_kpm_5m"].map(|column| {
+            format!("CAST(COALESCE(MAX({column}), 0) AS DOUBLE PRECISION) AS {column}")
+        }))
+        .collect();
+    format!(
+        "SELECT {aggregates} FROM {table} WHERE {time} >= $1 AND {time} < $2",
+        aggregates = aggregates.join(", "),
+        table = granularity.metrics_table(),
+        time = granularity.time_column(),
+    )
+}
+
+fn saved_intervals_sql() -> String {
+    format!(
+        "SELECT timestamp, interval_id, interval_start, {columns}, peak_kpm_1m, peak_kpm_5m \
+         FROM metrics WHERE timestamp >= $1 AND timestamp < $2 ORDER BY timestamp",
+        columns = ROLLUP_SUM_COLUMNS.join(", "),
+    )
+}
+
+fn oldest_sql(granularity: Granularity) -> String {
+    format!(
+        "SELECT MIN({time}) FROM {table}",
+        table = granularity.metrics_table(),
+        time = granularity.time_column(),
+    )
+}
+
+fn metrics_from_row<'r, R>(row: &'r R) -> Result<Metrics>
+where
+    R: Row,
+    &'static str: ColumnIndex<R>,
+    i64: Decode<'r, R::Database> + Type<R::Database>,
+    f64: Decode<'r, R::Database> + Type<R::Database>,
+{
+    let int = |column: &'static str| -> Result<i64> {
+        row.try_get(column).with_context(|| format!("Failed to get {}", column))
+    };
+    let float = |column: &'static str| -> Result<f64> {
+        row.try_get(column).with_context(|| format!("Failed to get {}", column))
+    };
+    Ok(Metrics {
+        keypresses: int("keypresses")?,
+        mouse_clicks: int("mouse_clicks")?,
+        mouse_distance_in: float("mouse_distance_in")?,
+        mouse_distance_mi: float("mouse_distance_mi")?,
+        scroll_steps: int("scroll_steps")?,
+        scroll_up: int("scroll_up")?,
+        scroll_down: int("scroll_down")?,
+        scroll_left: int("scroll_left")?,
+        scroll_right: int("scroll_right")?,
+        scroll_vertical_precise: float("scroll_vertical_precise")?,
+        scroll_horizontal_precise: float("scroll_horizontal_precise")?,
+        left_clicks: int("left_clicks")?,
+        right_clicks: int("right_clicks")?,
+        middle_clicks: int("middle_clicks")?,
+        extra_clicks: int("extra_clicks")?,
+        double_clicks: int("double_clicks")?,
+        triple_clicks: int("triple_clicks")?,
+        drags: int("drags")?,
+        drag_distance_in: float("drag_distance_in")?,
+        typing_keys: int("typing_keys")?,
+        typing_seconds: float("typing_seconds")?,
+        peak_kpm_1m: float("peak_kpm_1m")?,
+        peak_kpm_5m: float("peak_kpm_5m")?,
+        key_counts: HashMap::new(),
+    })
+}
+
+// `samples`, the summed columns and the peaks of the totals row, as
+// floats, in `TOTALS_COLUMNS` order.
+fn stored_totals_sql() -> String {
+    let columns: Vec<String> = TOTALS_COLUMNS
+        .iter()
+        .map(|column| format!("CAST({column} AS DOUBLE PRECISION)"))
+        .collect();
+    format!("SELECT {} FROM metrics_totals WHERE id = 1", columns.join(", "))
+}
+
+// The same values recomputed from raw rows, plus the daily rollups of days
+// before $1, the first day that still has raw rows. A NULL $1 means
+// everything has been compacted.
+fn recomputed_totals_sql() -> String {
+    let aggregates: Vec<String> = TOTALS_COLUMNS
+        .iter()
+        .map(|&column| {
+            let aggregate = if column.starts_with("peak_") { "MAX" } else { "SUM" };
+            format!("CAST(COALESCE({aggregate}({column}), 0) AS DOUBLE PRECISION)")
+        })
+        .collect();
+    let columns = ROLLUP_SUM_COLUMNS.join(", ");
+    format!(
+        "SELECT {aggregates} FROM ( \
+         SELECT 1 AS samples, {columns}, peak_kpm_1m, peak_kpm_5m FROM metrics \
+         UNION ALL \
+         SELECT samples, {columns}, peak_kpm_1m, peak_kpm_5m FROM metrics_daily \
+         WHERE $1 IS NULL OR bucket_start < $1 \
+         ) AS history",
+        aggregates = aggregates.join(", "),
+    )
+}
+
+// Every column of the totals row except its id.
+const TOTALS_COLUMNS: &[&str] = &[
+    "samples",
+    "keypresses",
+    "mouse_clicks",
+    "mouse_distance_in",
+    "mouse_distance_mi",
+    "scroll_steps",
+    "scroll_up",
+    "scroll_down",
+    "scroll_left",
+    "scroll_right",
+    "scroll_vertical_precise",
+    "scroll_horizontal_precise",
+    "left_clicks",
+    "right_clicks",
+    "middle_clicks",
+    "extra_clicks",
+    "double_clicks",
+    "triple_clicks",
+    "drags",
+    "drag_distance_in",
+    "typing_keys",
+    "typing_seconds",
+    "peak_kpm_1m",
+    "peak_kpm_5m",
+];
+
+/// A column of the stored totals that doesn't match history.
+#[derive(Debug, Clone)]
+pub struct TotalsDrift {
+    pub column: &'static str,
+    pub stored: f64,
+    pub recomputed: f64,
+}
+
+fn totals_drift(stored: &[f64], recomputed: &[f64]) -> Vec<TotalsDrift> {
+    TOTALS_COLUMNS
+        .iter()
+        .zip(stored.iter().zip(recomputed))
+        .filter(|(_, (stored, recomputed))| {
+            // Float columns pick up rounding error from being summed in a
+            // different order.
+            (*stored - *recomputed).abs() > 1e-6 * stored.abs().max(1.0)
+        })
+        .map(|(&column, (&stored, &recomputed))| TotalsDrift { column, stored, recomputed })
+        .collect()
+}
+
+/// A raw interval as it was saved. Intervals saved before they had ids
+/// have neither an id nor a start.
+pub struct SavedInterval {
+    pub id: Option<Uuid>,
+    pub start: Option<DateTime<Utc>>,
+    pub end: DateTime<Utc>,
+    pub metrics: Metrics,
+}
+
+/// An interval waiting to be uploaded.
+#[derive(Clone)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub interval: Interval,
+    /// Failed uploads so far.
+    pub attempts: i64,
+    /// Of those, the ones the sink answered without acknowledging it.
+    pub rejections: i64,
+}
+
+fn outbox_entry_from_row<'r, R>(row: &'r R) -> Result<OutboxEntry>
+where
+    R: Row,
+    &'static str: ColumnIndex<R>,
+    i64: Decode<'r, R::Database> + Type<R::Database>,
+    &'r str: Decode<'r, R::Database> + Type<R::Database>,
+{
+    let id = row.try_get("id").context("Failed to get outbox id")?;
+    let payload = row.try_get("payload").context("Failed to get outbox payload")?;
+    Ok(OutboxEntry {
+        id,
+        interval: serde_json::from_str(payload)
+            .with_context(|| format!("Failed to parse outbox entry {}", id))?,
+        attempts: row.try_get("attempts").context("Failed to get outbox attempts")?,
+        rejections: row.try_get("rejections").context("Failed to get outbox rejections")?,
+    })
+}
+
+/// A stretch of history saved without being queued for sync.
+#[derive(Debug, Clone)]
+pub struct SyncGap {
+    pub id: i64,
+    /// None for the history before sync first started.
+    pub start: Option<DateTime<Utc>>,
+    /// None while sync is still off.
+    pub end: Option<DateTime<Utc>>,
+    pub backfilled_until: Option<DateTime<Utc>>,
+}
+
+/// A backend that stores metrics. Ranges are `start` inclusive and `end`
+/// exclusive throughout. Where `end` is optional, leaving it out runs the
+/// range up to now.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// The timezone days, weeks and months start in.
+    fn timezone(&self) -> Tz;
+
+    /// Saves one interval, stamped with its end and the UTC offset of the
+    /// storage timezone at that time, and returns its row id. It's also
+    /// queued in the sync outbox for each of `sinks`.
+    async fn insert_metrics(&self, interval: &Interval, sinks: &[String]) -> Result<i64>;
+
+    /// Whether the interval with this id has been saved.
+    async fn has_interval(&self, id: Uuid) -> Result<bool>;
+
+    /// The most pressed keys in the range, most pressed first. `None`
+    /// returns every key.
+    async fn get_top_keys(
+        &self,
+        start: DateTime<Utc>,
+        end: Option<DateTime<Utc>>,
+        limit: Option<i64>,
+    ) -> Result<Vec<KeyCount>>;
+
+    async fn get_total_metrics(&self) -> Result<TotalMetrics>;
+
+    /// Every saved interval or `granularity` rollup bucket in the range,
+    /// after its start, oldest first. Key counts are left empty.
+    async fn get_metric_rows(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        granularity: Granularity,
+    ) -> Result<Vec<(DateTime<Utc>, Metrics)>>;
+
+    /// Every saved interval or `granularity` rollup bucket in the range,
+    /// summed together. Key counts are left empty.
+    async fn get_metric_sums(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        granularity: Granularity,
+    ) -> Result<Metrics>;
+
+    /// Every raw interval that ended in the range, with the id and start
+    /// it was saved with, oldest first. Key counts are left empty.
+    async fn get_saved_intervals(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<SavedInterval>>;
+
+    /// When the oldest interval or `granularity` rollup bucket still
+    /// stored starts.
+    async fn oldest(&self, granularity: Granularity) -> Result<Option<DateTime<Utc>>>;
+
+    /// Deletes raw intervals saved before `before`, whose data lives on in
+    /// the rollups. Returns how many were removed. `before` should be the
+    /// start of a UTC day so no day is left half raw, half rolled up.
+    async fn compact(&self, before: DateTime<Utc>) -> Result<u64>;
+
+    /// Deletes hourly rollup buckets starting before `before`, leaving the
+    /// daily rollups. Returns how many were removed.
+    async fn prune_hourly(&self, before: DateTime<Utc>) -> Result<u64>;
+
+    /// Gives the space freed by deleted rows back to the filesystem.
+    async fn vacuum(&self) -> Result<()>;
+
+    /// Recomputes the all-time totals from history and returns the columns
+    /// where the stored totals disagree.
+    async fn verify_totals(&self) -> Result<Vec<TotalsDrift>>;
+
+    /// The oldest intervals waiting in the sync outbox for `sink`, up to
+    /// `limit`.
+    async fn pending_sync(&self, sink: &str, limit: i64) -> Result<Vec<OutboxEntry>>;
+
+    /// Removes an uploaded interval from the sync outbox.
+    async fn complete_sync(&self, id: i64) -> Result<()>;
+
+    /// Records a failed attempt to upload an interval in the sync outbox.
+    async fn record_sync_failure(&self, id: i64, error: &str) -> Result<()>;
+
+    /// Records that the sink answered without acknowledging an interval in
+    /// the sync outbox. A dead-lettered interval stays in the outbox but is
+    /// no longer pending.
+    async fn record_sync_rejection(&self, id: i64, error: &str, dead_letter: bool) -> Result<()>;
+
+    /// The stretches of history saved without being queued for sync,
+    /// oldest first. There are none until sync first starts.
+    async fn sync_gaps(&self) -> Result<Vec<SyncGap>>;
+
+    /// Records that intervals are queued for sync from `time` on. The first
+    /// time, that leaves the history before it as a gap; after that, it
+    /// ends the gap `pause_sync` opened, if any.
+    async fn start_sync(&self, time: DateTime<Utc>) -> Result<()>;
+
+    /// Records that intervals aren't queued for sync from `time` on, unless
+    /// sync has never started or is already off.
+    async fn pause_sync(&self, time: DateTime<Utc>) -> Result<()>;
+
+    /// Queues `intervals` from the sync gap `gap` in the sync outbox for
+    /// `sink`, and records that the gap has been backfilled up to `until`,
+    /// both or neither.
+    async fn queue_backfill(&self, sink: &str, intervals: &[Interval], gap: i64, until: DateTime<Utc>) -> Result<()>;
+
+    /// Inserts `session`, or moves the end of the stored session that
+    /// started at the same time.
+    async fn save_session(&self, session: &Session) -> Result<()>;
+
+    /// Sessions overlapping the range, oldest first.
+    async fn get_sessions(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Session>>;
+
+    async fn insert_break_reminder(&self, time: DateTime<Utc>, active_minutes: f64) -> Result<i64>;
+
+    async fn resolve_break_reminder(&self, id: i64, outcome: BreakOutcome, time: DateTime<Utc>) -> Result<()>;
+
+    /// When each reminder in the range was shown and its outcome.
+    async fn get_break_outcomes(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, String)>>;
+
+    /// The finest granularity with history from `start` on.
+    async fn finest_granularity(&self, start: DateTime<Utc>) -> Result<Granularity> {
+        Ok(Granularity::finest_since(
+            start,
+            self.oldest(Granularity::Raw).await?,
+            self.oldest(Granularity::Hourly).await?,
+            self.oldest(Granularity::Daily).await?,
+        ))
+    }
+
+    /// The granularity queries over the range read from.
+    async fn granularity(&self, start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> Result<Granularity> {
+        let finest = self.finest_granularity(start).await?;
+        Ok(Granularity::for_range(start, end, finest))
+    }
+
+    /// Metrics in the range summed into `bucket`s, after the start of each,
+    /// oldest first. Buckets with nothing saved are left out. Where only
+    /// rollups coarser than the bucket remain, each lands in the bucket its
+    /// start falls in.
+    async fn get_series(
+        &self,
+        start: DateTime<Utc>,
+        end: Option<DateTime<Utc>>,
+        bucket: Bucket,
+    ) -> Result<Vec<(DateTime<Utc>, Metrics)>> {
+        let granularity = self
+            .granularity(start, end)
+            .await?
+            .min(bucket.granularity())
+            .max(self.finest_granularity(start).await?);
+        let end = end.unwrap_or_else(Utc::now);
+        let mut series: Vec<(DateTime<Utc>, Metrics)> = Vec::new();
+        for (time, metrics) in self.get_metric_rows(start, end, granularity).await? {
+            // A daily rollup stands in for the local day with its date.
+            let time = match granularity {
+                Granularity::Daily => local_midnight(time.date_naive(), self.timezone()),
+                _ => time,
+            };
+            let bucket_start = bucket.start_of(time, self.timezone());
+            match series.last_mut() {
+                Some((last, total)) if *last == bucket_start => total.add(&metrics),
+                _ => series.push((bucket_start, metrics)),
+            }
+        }
+        Ok(series)
+    }
+
+    /// Metrics in the range summed together.
+    async fn get_range_totals(&self, start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> Result<Metrics> {
+        let granularity = self.granularity(start, end).await?;
+        self.get_metric_sums(start, end.unwrap_or_else(Utc::now), granularity).await
+    }
+
+    /// Metrics so far in the current day, week or month.
+    async fn get_period_totals(&self, period: Period) -> Result<Metrics> {
+        self.get_range_totals(period.start(Utc::now(), self.timezone()), None).await
+    }
+
+    /// Press counts for every key pressed in the range.
+    async fn get_key_counts(&self, start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> Result<Vec<KeyCount>> {
+        self.get_top_keys(start, end, None).await
+    }
+
+    /// Average and peak typing rates for each local day with metrics in
+    /// the range.
+    async fn get_typing_speed_by_day(
+        &self,
+        start: DateTime<Utc>,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<Vec<(NaiveDate, TypingSpeed)>> {
+        Ok(self
+            .get_series(start, end, Bucket::Day)
+            .await?
+            .into_iter()
+            .map(|(day, metrics)| (day.with_timezone(&self.timezone()).date_naive(), metrics.typing_speed()))
+            .collect())
+    }
+
+    /// Active minutes for each local day with activity in the range.
+    /// Sessions spanning midnight are split between the days.
+    async fn get_active_minutes_by_day(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<DailyActivity>> {
+        let mut days: Vec<DailyActivity> = Vec::new();
+        for session in self.get_sessions(start, end).await? {
+            let mut from = session.start.max(start);
+            let to = session.end.min(end);
+
+            while from < to {
+                let date = from.with_timezone(&self.timezone()).date_naive();
+                let split = next_local_midnight(date, self.timezone()).min(to);
+                let minutes = (split - from).num_milliseconds() as f64 / 60_000.0;
+                match days.last_mut() {
+                    Some(day) if day.date == date => day.active_minutes += minutes,
+                    _ => days.push(DailyActivity { date, active_minutes: minutes }),
+                }
+                from = split;
+            }
+        }
+
+        Ok(days)
+    }
+
+    /// Break reminder outcomes for each local day with reminders in the
+    /// range.
+    async fn get_break_compliance_by_day(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(NaiveDate, BreakCompliance)>> {
+        let mut days: BTreeMap<NaiveDate, BreakCompliance> = BTreeMap::new();
+        for (reminded_at, outcome) in self.get_break_outcomes(start, end).await? {
+            let day = days.entry(reminded_at.with_timezone(&self.timezone()).date_naive()).or_default();
+            day.reminders += 1;
+            if outcome == BreakOutcome::Taken.as_str() {
+                day.taken += 1;
+            } else if outcome == BreakOutcome::Snoozed.as_str() {
+                day.snoozed += 1;
+            } else if outcome == BreakOutcome::Skipped.as_str() {
+                day.skipped += 1;
+            }
+        }
+
+        Ok(days.into_iter().collect())
+    }
+}
+
+/// What one backend's SQL does differently from another's, for the
+/// queries `SqlStorage` shares between them.
+pub trait Dialect: Database {
+    /// The two-argument maximum.
+    const GREATEST: &'static str;
+    /// What to bind to `LIMIT` for no limit.
+    const NO_LIMIT: Option<i64>;
+    /// Whether sqlx's `_sqlx_migrations` table exists, as a boolean.
+    const HAS_MIGRATIONS_TABLE: &'static str;
+
+    fn rows_affected(result: &Self::QueryResult) -> u64;
+}
+
+// The highest applied migration, or None if the database predates
+// migrations (or is new).
+async fn schema_version<DB>(pool: &Pool<DB>) -> Result<Option<i64>>
+where
+    DB: Dialect,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    usize: ColumnIndex<DB::Row>,
+    for<'q> bool: Decode<'q, DB> + Type<DB>,
+    for<'q> i64: Decode<'q, DB> + Type<DB>,
+{
+    let tracked: bool = sqlx::query_scalar(DB::HAS_MIGRATIONS_TABLE)
+        .fetch_one(pool)
+        .await
+        .context("Failed to check for migrations table")?;
+    if !tracked {
+        return Ok(None);
+    }
+
+    let version: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+            .fetch_one(pool)
+            .await
+            .context("Failed to read schema version")?;
+    Ok(version)
+}
+
+/// Brings the schema up to the newest migration this binary knows about,
+/// refusing databases written by a newer version.
+async fn migrate<DB>(pool: &Pool<DB>, migrator: &Migrator) -> Result<()>
+where
+    DB: Dialect,
+    DB::Connection: Migrate,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    usize: ColumnIndex<DB::Row>,
+    for<'q> bool: Decode<'q, DB> + Type<DB>,
+    for<'q> i64: Decode<'q, DB> + Type<DB>,
+{
+    let latest = migrator.iter().map(|migration| migration.version).max().unwrap_or(0);
+    if let Some(version) = schema_version(pool).await?.filter(|&version| version > latest) {
+        anyhow::bail!(
+            "Database schema version {} is newer than this build supports ({}); \
+             upgrade kweeb-logger to open it",
+            version,
+            latest
+        );
+    }
+
+    migrator.run(pool).await.context("Failed to migrate database")?;
+    log::info!("Database schema at version {}", latest);
+
+    Ok(())
+}
+
+/// A `Storage` on an sqlx database. Each backend only adds how to connect
+/// and migrate, and its `Dialect`.
+pub struct SqlStorage<DB: Database> {
+    pool: Pool<DB>,
+    timezone: Tz,
+}
+
+impl<DB: Database> SqlStorage<DB> {
+    fn pool(&self) -> &Pool<DB> {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl<DB> Storage for SqlStorage<DB>
+where
+    DB: Dialect,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    usize: ColumnIndex<DB::Row>,
+    &'static str: ColumnIndex<DB::Row>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    for<'q> i64: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+    for<'q> f64: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+    for<'q> &'q str: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+    for<'q> NaiveDateTime: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+    for<'q> Option<i64>: Encode<'q, DB>,
+    for<'q> Option<NaiveDateTime>: Encode<'q, DB>,
+{
+    fn timezone(&self) -> Tz {
+        self.timezone
+    }
+
+    async fn insert_metrics(&self, interval: &Interval, sinks: &[String]) -> Result<i64> {
+        let metrics = &interval.metrics;
+        let timestamp = interval.end;
+        let utc_offset_seconds = self.timezone.offset_from_utc_datetime(&timestamp.naive_utc()).fix().local_minus_utc();
+        let mut tx = self.pool.begin().await.context("Failed to begin transaction")?;
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO metrics 
+            (keypresses, mouse_clicks, mouse_distance_in, mouse_distance_mi, scroll_steps,
+             scroll_up, scroll_down, scroll_left, scroll_right,
+             scroll_vertical_precise, scroll_horizontal_precise,
+             left_clicks, right_clicks, middle_clicks, extra_clicks,
+             double_clicks, triple_clicks, drags, drag_distance_in,
+             typing_keys, typing_seconds, typing_kpm, typing_wpm, peak_kpm_1m, peak_kpm_5m,
+             timestamp, utc_offset_seconds, interval_id, interval_start)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11,
+                    $12, $13, $14, $15, $16, $17, $18, $19,
+                    $20, $21, $22, $23, $24, $25, $26, $27, $28, $29)
+            RETURNING id
+            "#,
+        )
+        .bind(metrics.keypresses)
+        .bind(metrics.mouse_clicks)
+        .bind(metrics.mouse_distance_in)
+        .bind(metrics.mouse_distance_mi)
+        .bind(metrics.scroll_steps)
+        .bind(metrics.scroll_up)
+        .bind(metrics.scroll_down)
+        .bind(metrics.scroll_left)
+        .bind(metrics.scroll_right)
+        .bind(metrics.scroll_vertical_precise)
+        .bind(metrics.scroll_horizontal_precise)
+        .bind(metrics.left_clicks)
+        .bind(metrics.right_clicks)
+        .bind(metrics.middle_clicks)
+        .bind(metrics.extra_clicks)
+        .bind(metrics.double_clicks)
+        .bind(metrics.triple_clicks)
+        .bind(metrics.drags)
+        .bind(metrics.drag_distance_in)
+        .bind(metrics.typing_keys)
+        .bind(metrics.typing_seconds)
+        .bind(metrics.typing_kpm())
+        .bind(metrics.typing_wpm())
+        .bind(metrics.peak_kpm_1m)
+        .bind(metrics.peak_kpm_5m)
+        .bind(timestamp.naive_utc())
+        .bind(utc_offset_seconds)
+        .bind(interval.id.to_string())
+        .bind(interval.start.naive_utc())
+        .fetch_one(&mut *tx)
+        .await
+        .context("Failed to insert metrics")?;
+        let id: i64 = row.try_get(0).context("Failed to get metrics id")?;
+
+        for (key, count) in &metrics.key_counts {
+            sqlx::query("INSERT INTO key_counts (metrics_id, keycode, count) VALUES ($1, $2, $3)")
+                .bind(id)
+                .bind(key.to_string())
+                .bind(count)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to insert key counts")?;
+        }
+
+        for granularity in [Granularity::Hourly, Granularity::Daily] {
+            let bucket = granularity.bucket_start(timestamp).naive_utc();
+            sqlx::query(&accumulate_sql(granularity.metrics_table(), "bucket_start", DB::GREATEST))
+                .bind(bucket)
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to update metrics rollup")?;
+
+            let key_counts_upsert = key_counts_upsert_sql(granularity);
+            for (key, count) in &metrics.key_counts {
+                sqlx::query(&key_counts_upsert)
+                    .bind(bucket)
+                    .bind(key.to_string())
+                    .bind(count)
+                    .execute(&mut *tx)
+                    .await
+                    .context("Failed to update key counts rollup")?;
+            }
+        }
+
+        sqlx::query(&accumulate_sql("metrics_totals", "id", DB::GREATEST))
+            .bind(1)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to update metrics totals")?;
+
+        if !sinks.is_empty() {
+            let payload = serde_json::to_string(interval)?;
+            for sink in sinks {
+                sqlx::query("INSERT INTO sync_outbox (created_at, sink, payload) VALUES ($1, $2, $3)")
+                    .bind(timestamp.naive_utc())
+                    .bind(sink)
+                    .bind(&payload)
+                    .execute(&mut *tx)
+                    .await
+                    .context("Failed to queue metrics for sync")?;
+            }
+        }
+
+        tx.commit().await.context("Failed to commit metrics")?;
+
+        Ok(id)
+    }
+
+    async fn has_interval(&self, id: Uuid) -> Result<bool> {
+        let row = sqlx::query("SELECT 1 FROM metrics WHERE interval_id = $1")
+            .bind(id.to_string())
+            .fetch_optional(self.pool())
+            .await
+            .context("Failed to look up interval")?;
+        Ok(row.is_some())
+    }
+
+    async fn get_top_keys(
+        &self,
+        start: DateTime<Utc>,
+        end: Option<DateTime<Utc>>,
+        limit: Option<i64>,
+    ) -> Result<Vec<KeyCount>> {
+        let granularity = self.granularity(start, end).await?;
+        let end = end.unwrap_or_else(Utc::now);
+        let rows = sqlx::query(&key_counts_sql(granularity))
+            .bind(start.naive_utc())
+            .bind(end.naive_utc())
+            .bind(limit.or(DB::NO_LIMIT))
+            .fetch_all(self.pool())
+            .await
+            .context("Failed to fetch top keys")?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(KeyCount {
+                    key: row.try_get(0).context("Failed to get keycode")?,
+                    count: row.try_get(1).context("Failed to get key count")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn get_total_metrics(&self) -> Result<TotalMetrics> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                keypresses,
+                mouse_clicks,
+                mouse_distance_in,
+                mouse_distance_mi,
+                scroll_steps,
+                left_clicks,
+                right_clicks,
+                middle_clicks,
+                extra_clicks,
+                double_clicks,
+                triple_clicks,
+                drags,
+                drag_distance_in
+            FROM metrics_totals
+            WHERE id = 1
+            "#
+        )
+        .fetch_one(self.pool())
+        .await
+        .context("Failed to fetch total metrics")?;
+
+        Ok(TotalMetrics {
+            total_keypresses: row.try_get(0)
+                .context("Failed to get total_keypresses")?,
+            total_mouse_clicks: row.try_get(1)
+                .context("Failed to get total_mouse_clicks")?,
+            total_mouse_distance_in: row.try_get(2)
+                .context("Failed to get total_mouse_distance_in")?,
+            total_mouse_distance_mi: row.try_get(3)
+                .context("Failed to get total_mouse_distance_mi")?,
+            total_scroll_steps: row.try_get(4)
+                .context("Failed to get total_scroll_steps")?,
+            total_left_clicks: row.try_get(5)
+                .context("Failed to get total_left_clicks")?,
+            total_right_clicks: row.try_get(6)
+                .context("Failed to get total_right_clicks")?,
+            total_middle_clicks: row.try_get(7)
+                .context("Failed to get total_middle_clicks")?,
+            total_extra_clicks: row.try_get(8)
+                .context("Failed to get total_extra_clicks")?,
+            total_double_clicks: row.try_get(9)
+                .context("Failed to get total_double_clicks")?,
+            total_triple_clicks: row.try_get(10)
+                .context("Failed to get total_triple_clicks")?,
+            total_drags: row.try_get(11)
+                .context("Failed to get total_drags")?,
+            total_drag_distance_in: row.try_get(12)
+                .context("Failed to get total_drag_distance_in")?,
+        })
+    }
+
+    async fn get_metric_rows(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        granularity: Granularity,
+    ) -> Result<Vec<(DateTime<Utc>, Metrics)>> {
+        let rows = sqlx::query(&metric_rows_sql(granularity))
+            .bind(start.naive_utc())
+            .bind(end.naive_utc())
+            .fetch_all(self.pool())
+            .await
+            .context("Failed to fetch metric rows")?;
+
+        rows.iter()
+            .map(|row| {
+                let time: NaiveDateTime = row.try_get(0).context("Failed to get timestamp")?;
+                Ok((Utc.from_utc_datetime(&time), metrics_from_row(row)?))
+            })
+            .collect()
+    }
+
+    async fn get_metric_sums(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        granularity: Granularity,
+    ) -> Result<Metrics> {
+        let row = sqlx::query(&metric_sums_sql(granularity))
+            .bind(start.naive_utc())
+            .bind(end.naive_utc())
+            .fetch_one(self.pool())
+            .await
+            .context("Failed to sum metric rows")?;
+        metrics_from_row(&row)
+    }
+
+    async fn get_saved_intervals(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<SavedInterval>> {
+        let rows = sqlx::query(&saved_intervals_sql())
+            .bind(start.naive_utc())
+            .bind(end.naive_utc())
+            .fetch_all(self.pool())
+            .await
+            .context("Failed to fetch saved intervals")?;
+
+        rows.iter()
+            .map(|row| {
+                let end: NaiveDateTime = row.try_get("timestamp").context("Failed to get timestamp")?;
+                let id: Option<String> = row.try_get("interval_id").context("Failed to get interval_id")?;
+                let start: Option<NaiveDateTime> =
+                    row.try_get("interval_start").context("Failed to get interval_start")?;
+                Ok(SavedInterval {
+                    id: id
+                        .map(|id| id.parse())
+                        .transpose()
+                        .with_context(|| format!("Invalid interval id for interval ending {}", end))?,
+                    start: start.map(|start| Utc.from_utc_datetime(&start)),
+                    end: Utc.from_utc_datetime(&end),
+                    metrics: metrics_from_row(row)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn oldest(&self, granularity: Granularity) -> Result<Option<DateTime<Utc>>> {
+        let oldest: Option<NaiveDateTime> = sqlx::query_scalar(&oldest_sql(granularity))
+            .fetch_one(self.pool())
+            .await
+            .context("Failed to fetch oldest metrics timestamp")?;
+        Ok(oldest.map(|oldest| Utc.from_utc_datetime(&oldest)))
+    }
+
+    async fn compact(&self, before: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM metrics WHERE timestamp < $1")
+            .bind(before.naive_utc())
+            .execute(self.pool())
+            .await
+            .context("Failed to compact metrics")?;
+        Ok(DB::rows_affected(&result))
+    }
+
+    async fn prune_hourly(&self, before: DateTime<Utc>) -> Result<u64> {
+        let mut tx = self.pool.begin().await.context("Failed to begin transaction")?;
+        let result = sqlx::query("DELETE FROM metrics_hourly WHERE bucket_start < $1")
+            .bind(before.naive_utc())
+            .execute(&mut *tx)
+            .await
+            .context("Failed to prune hourly metrics")?;
+        sqlx::query("DELETE FROM key_counts_hourly WHERE bucket_start < $1")
+            .bind(before.naive_utc())
+            .execute(&mut *tx)
+            .await
+            .context("Failed to prune hourly key counts")?;
+        tx.commit().await.context("Failed to commit pruning")?;
+        Ok(DB::rows_affected(&result))
+    }
+
+    async fn vacuum(&self) -> Result<()> {
+        sqlx::query("VACUUM")
+            .execute(self.pool())
+            .await
+            .context("Failed to vacuum database")?;
+        Ok(())
+    }
+
+    async fn verify_totals(&self) -> Result<Vec<TotalsDrift>> {
+        let first_raw_day = self
+            .oldest(Granularity::Raw)
+            .await?
+            .map(|oldest| Granularity::Daily.bucket_start(oldest).naive_utc());
+
+        let stored = sqlx::query(&stored_totals_sql())
+            .fetch_one(self.pool())
+            .await
+            .context("Failed to fetch stored totals")?;
+        let recomputed = sqlx::query(&recomputed_totals_sql())
+            .bind(first_raw_day)
+            .fetch_one(self.pool())
+            .await
+            .context("Failed to recompute totals")?;
+
+        let stored: Vec<f64> = (0..stored.len())
+            .map(|i| stored.try_get(i).context("Failed to get stored total"))
+            .collect::<Result<_>>()?;
+        let recomputed: Vec<f64> = (0..recomputed.len())
+            .map(|i| recomputed.try_get(i).context("Failed to get recomputed total"))
+            .collect::<Result<_>>()?;
+
+        Ok(totals_drift(&stored, &recomputed))
+    }
+
+    async fn pending_sync(&self, sink: &str, limit: i64) -> Result<Vec<OutboxEntry>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, payload, attempts, rejections
+            FROM sync_outbox
+            WHERE sink = $1 AND dead_lettered_at IS NULL
+            ORDER BY id
+            LIMIT $2
+            "#,
+        )
+        .bind(sink)
+        .bind(limit)
+        .fetch_all(self.pool())
+        .await
+        .context("Failed to fetch sync outbox")?;
+
+        rows.iter().map(outbox_entry_from_row).collect()
+    }
+
+    async fn complete_sync(&self, id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM sync_outbox WHERE id = $1")
+            .bind(id)
+            .execute(self.pool())
+            .await
+            .context("Failed to remove synced metrics from outbox")?;
+        Ok(())
+    }
+
+    async fn record_sync_failure(&self, id: i64, error: &str) -> Result<()> {
+        sqlx::query("UPDATE sync_outbox SET attempts = attempts + 1, last_error = $2 WHERE id = $1")
+            .bind(id)
+            .bind(error)
+            .execute(self.pool())
+            .await
+            .context("Failed to record sync failure")?;
+        Ok(())
+    }
+
+    async fn record_sync_rejection(&self, id: i64, error: &str, dead_letter: bool) -> Result<()> {
+        let dead_lettered_at = dead_letter.then(|| Utc::now().naive_utc());
+        sqlx::query(
+            r#"
+            UPDATE sync_outbox
+            SET attempts = attempts + 1, rejections = rejections + 1, last_error = $2,
+                dead_lettered_at = $3
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(error)
+        .bind(dead_lettered_at)
+        .execute(self.pool())
+        .await
+        .context("Failed to record sync rejection")?;
+        Ok(())
+    }
+
+    async fn sync_gaps(&self) -> Result<Vec<SyncGap>> {
+        let rows = sqlx::query("SELECT id, started_at, ended_at, backfilled_until FROM sync_gaps ORDER BY id")
+            .fetch_all(self.pool())
+            .await
+            .context("Failed to fetch sync gaps")?;
+        rows.iter()
+            .map(|row| {
+                let time = |column: &'static str| -> Result<Option<DateTime<Utc>>> {
+                    let time: Option<NaiveDateTime> =
+                        row.try_get(column).with_context(|| format!("Failed to get {}", column))?;
+                    Ok(time.map(|time| Utc.from_utc_datetime(&time)))
+                };
+                Ok(SyncGap {
+                    id: row.try_get("id").context("Failed to get sync gap id")?,
+                    start: time("started_at")?,
+                    end: time("ended_at")?,
+                    backfilled_until: time("backfilled_until")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn start_sync(&self, time: DateTime<Utc>) -> Result<()> {
+        let mut tx = self.pool.begin().await.context("Failed to begin transaction")?;
+        sqlx::query("INSERT INTO sync_gaps (ended_at) SELECT $1 WHERE NOT EXISTS (SELECT 1 FROM sync_gaps)")
+            .bind(time.naive_utc())
+            .execute(&mut *tx)
+            .await
+            .context("Failed to record sync start")?;
+        sqlx::query("UPDATE sync_gaps SET ended_at = $1 WHERE ended_at IS NULL")
+            .bind(time.naive_utc())
+            .execute(&mut *tx)
+            .await
+            .context("Failed to record sync start")?;
+        tx.commit().await.context("Failed to commit sync start")?;
+        Ok(())
+    }
+
+    async fn pause_sync(&self, time: DateTime<Utc>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO sync_gaps (started_at)
+            SELECT $1
+            WHERE EXISTS (SELECT 1 FROM sync_gaps)
+              AND NOT EXISTS (SELECT 1 FROM sync_gaps WHERE ended_at IS NULL)
+            "#,
+        )
+        .bind(time.naive_utc())
+        .execute(self.pool())
+        .await
+        .context("Failed to record sync pause")?;
+        Ok(())
+    }
+
+    async fn queue_backfill(&self, sink: &str, intervals: &[Interval], gap: i64, until: DateTime<Utc>) -> Result<()> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await.context("Failed to begin transaction")?;
+        for interval in intervals {
+            sqlx::query("INSERT INTO sync_outbox (created_at, sink, payload) VALUES ($1, $2, $3)")
+                .bind(now.naive_utc())
+                .bind(sink)
+                .bind(serde_json::to_string(interval)?)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to queue history for sync")?;
+        }
+        sqlx::query("UPDATE sync_gaps SET backfilled_until = $1 WHERE id = $2")
+            .bind(until.naive_utc())
+            .bind(gap)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to record backfill progress")?;
+        tx.commit().await.context("Failed to commit backfill")?;
+        Ok(())
+    }
+
+    async fn insert_break_reminder(&self, time: DateTime<Utc>, active_minutes: f64) -> Result<i64> {
+        sqlx::query_scalar(
+            r#"
+            INSERT INTO break_reminders (reminded_at, active_minutes, outcome)
+            VALUES ($1, $2, $3)
+            RETURNING id
+            "#,
+        )
+        .bind(time.naive_utc())
+        .bind(active_minutes)
+        .bind(BreakOutcome::Pending.as_str())
+        .fetch_one(self.pool())
+        .await
+        .context("Failed to insert break reminder")
+    }
+
+    async fn resolve_break_reminder(
+        &self,
+        id: i64,
+        outcome: BreakOutcome,
+        time: DateTime<Utc>,
+    ) -> Result<()> {
+        sqlx::query("UPDATE break_reminders SET outcome = $1, resolved_at = $2 WHERE id = $3")
+            .bind(outcome.as_str())
+            .bind(time.naive_utc())
+            .bind(id)
+            .execute(self.pool())
+            .await
+            .context("Failed to update break reminder")?;
+
+        Ok(())
+    }
+
+    async fn get_break_outcomes(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, String)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT reminded_at, outcome
+            FROM break_reminders
+            WHERE reminded_at >= $1 AND reminded_at < $2
+            ORDER BY reminded_at
+            "#,
+        )
+        .bind(start.naive_utc())
+        .bind(end.naive_utc())
+        .fetch_all(self.pool())
+        .await
+        .context("Failed to fetch break reminders")?;
+
+        rows.iter()
+            .map(|row| {
+                let reminded_at: NaiveDateTime = row.try_get(0).context("Failed to get reminded_at")?;
+                let outcome: String = row.try_get(1).context("Failed to get outcome")?;
+                Ok((Utc.from_utc_datetime(&reminded_at), outcome))
+            })
+            .collect()
+    }
+
+    async fn save_session(&self, session: &Session) -> Result<()> {
+        let mut tx = self.pool.begin().await.context("Failed to begin transaction")?;
+
+        // A session is saved again each time it grows, so only the time
+        // since it was last saved is new.
+        let saved_end: Option<NaiveDateTime> =
+            sqlx::query_scalar("SELECT end_time FROM activity_sessions WHERE start_time = $1")
+                .bind(session.start.naive_utc())
+                .fetch_optional(&mut *tx)
+                .await
+                .context("Failed to fetch activity session")?;
+        let counted_until = saved_end.map_or(session.start, |end| Utc.from_utc_datetime(&end));
+
+        sqlx::query(
+            r#"
+            INSERT INTO activity_sessions (start_time, end_time)
+            VALUES ($1, $2)
+            ON CONFLICT (start_time) DO UPDATE SET end_time = excluded.end_time
+            "#,
+        )
+        .bind(session.start.naive_utc())
+        .bind(session.end.naive_utc())
+        .execute(&mut *tx)
+        .await
+        .context("Failed to save activity session")?;
+
+        let minutes = (session.end - counted_until).num_milliseconds() as f64 / 60_000.0;
+        sqlx::query("UPDATE metrics_totals SET active_minutes = active_minutes + $1 WHERE id = 1")
+            .bind(minutes)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to update active minutes total")?;
+
+        tx.commit().await.context("Failed to commit activity session")?;
+
+        Ok(())
+    }
+
+    async fn get_sessions(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Session>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT start_time, end_time
+            FROM activity_sessions
+            WHERE end_time > $1 AND start_time < $2
+            ORDER BY start_time
+            "#,
+        )
+        .bind(start.naive_utc())
+        .bind(end.naive_utc())
+        .fetch_all(self.pool())
+        .await
+        .context("Failed to fetch activity sessions")?;
+
+        rows.iter()
+            .map(|row| {
+                let start: NaiveDateTime = row.try_get(0).context("Failed to get start_time")?;
+                let end: NaiveDateTime = row.try_get(1).context("Failed to get end_time")?;
+                Ok(Session {
+                    start: Utc.from_utc_datetime(&start),
+                    end: Utc.from_utc_datetime(&end),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Opens the backend `config` asks for, creating and migrating its schema
+/// as needed.
+pub async fn open(config: &DBConfig) -> Result<Arc<dyn Storage>> {
+    let timezone = resolve_timezone(config.timezone.as_deref())?;
+    match config.db_type.as_str() {
+        "" | "sqlite" => {
+            let path = match &config.filepath {
+                Some(filepath) => PathBuf::from(filepath),
+                None => default_database_path()?,
+            };
+            Ok(Arc::new(SqliteStorage::open(&path, timezone).await?))
+        }
+        "postgres" | "postgresql" => {
+            let url = config
+                .url
+                .as_deref()
+                .context("database.url is required when db_type is postgres")?;
+            Ok(Arc::new(PostgresStorage::open(url, timezone).await?))
+        }
+        other => anyhow::bail!("Unsupported database type `{}`", other),
+    }
+}
+
+fn default_database_path() -> Result<PathBuf> {
+    let proj_dirs = ProjectDirs::from("com", "kweeb-logger", "logger")
+        .context("Failed to get project directories")?;
+
+    let data_dir = proj_dirs.data_dir();
+    std::fs::create_dir_all(data_dir)?;
+
+    Ok(data_dir.join("kweeb-logger.db"))
+}
+
+// The configured timezone, or the system's if none is.
+fn resolve_timezone(name: Option<&str>) -> Result<Tz> {
+    if let Some(name) = name {
+        return name
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Unknown timezone `{}`: {}", name, e));
+    }
+    match iana_time_zone::get_timezone().map(|name| name.parse::<Tz>()) {
+        Ok(Ok(timezone)) => Ok(timezone),
+        Ok(Err(e)) => {
+            log::warn!("Unknown system timezone, using UTC: {}", e);
+            Ok(Tz::UTC)
+        }
+        Err(e) => {
+            log::warn!("Failed to get system timezone, using UTC: {}", e);
+            Ok(Tz::UTC)
+        }
+    }
+}
+
+fn next_local_midnight(date: NaiveDate, timezone: Tz) -> DateTime<Utc> {
+    local_midnight(date.succ_opt().unwrap_or(date), timezone)
+}
+
+/// The instant the day `date` starts in `timezone`.
+pub fn local_midnight(date: NaiveDate, timezone: Tz) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+    timezone
+        .from_local_datetime(&midnight)
+        .earliest()
+        // Midnight doesn't exist on some DST transition days; the hour after
+        // it always does.
+        .or_else(|| timezone.from_local_datetime(&(midnight + Duration::hours(1))).earliest())
+        .map(|time| time.with_timezone(&Utc))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use device_query::Keycode;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn days_are_23_and_25_hours_across_dst() {
+        let berlin = chrono_tz::Europe::Berlin;
+        let spring = NaiveDate::from_ymd_opt(2026, 3, 29).unwrap();
+        assert_eq!(local_midnight(spring, berlin), utc("2026-03-28T23:00:00Z"));
+        assert_eq!(next_local_midnight(spring, berlin) - local_midnight(spring, berlin), Duration::hours(23));
+
+        let autumn = NaiveDate::from_ymd_opt(2026, 10, 25).unwrap();
+        assert_eq!(local_midnight(autumn, berlin), utc("2026-10-24T22:00:00Z"));
+        assert_eq!(next_local_midnight(autumn, berlin) - local_midnight(autumn, berlin), Duration::hours(25));
+    }
+
+    #[test]
+    fn day_without_midnight_starts_at_one() {
+        // São Paulo sprang forward from midnight to 01:00.
+        let sao_paulo = chrono_tz::America::Sao_Paulo;
+        let date = NaiveDate::from_ymd_opt(2018, 11, 4).unwrap();
+        assert_eq!(local_midnight(date, sao_paulo), utc("2018-11-04T03:00:00Z"));
+        assert_eq!(Bucket::Day.start_of(utc("2018-11-04T15:00:00Z"), sao_paulo), utc("2018-11-04T03:00:00Z"));
+    }
+
+    #[test]
+    fn open_range_reads_the_coarsest_rollup() {
+        let day = utc("2026-03-02T00:00:00Z");
+        let hour = utc("2026-03-02T09:00:00Z");
+        let now = utc("2026-03-02T09:41:17Z");
+
+        assert_eq!(Granularity::for_range(day, None, Granularity::Raw), Granularity::Daily);
+        assert_eq!(Granularity::for_range(hour, None, Granularity::Raw), Granularity::Hourly);
+        assert_eq!(Granularity::for_range(now, None, Granularity::Raw), Granularity::Raw);
+
+        // The same ranges with an end part-way through a bucket.
+        assert_eq!(Granularity::for_range(day, Some(now), Granularity::Raw), Granularity::Raw);
+        assert_eq!(Granularity::for_range(day, Some(hour), Granularity::Raw), Granularity::Hourly);
+        assert_eq!(Granularity::for_range(day, Some(now), Granularity::Hourly), Granularity::Hourly);
+    }
+
+    #[test]
+    fn buckets_across_dst() {
+        let berlin = chrono_tz::Europe::Berlin;
+        // 03:30 CEST, just after the clocks went forward.
+        let spring = utc("2026-03-29T01:30:00Z");
+        assert_eq!(Bucket::Hour.start_of(spring, berlin), utc("2026-03-29T01:00:00Z"));
+        assert_eq!(Bucket::Day.start_of(spring, berlin), utc("2026-03-28T23:00:00Z"));
+        assert_eq!(Bucket::Week.start_of(spring, berlin), utc("2026-03-22T23:00:00Z"));
+        assert_eq!(Bucket::Month.start_of(spring, berlin), utc("2026-02-28T23:00:00Z"));
+
+        // 02:30 happens twice when the clocks go back.
+        let first = utc("2026-10-25T00:30:00Z");
+        let second = utc("2026-10-25T01:30:00Z");
+        assert_eq!(Bucket::Hour.start_of(first, berlin), utc("2026-10-25T00:00:00Z"));
+        assert_eq!(Bucket::Hour.start_of(second, berlin), utc("2026-10-25T01:00:00Z"));
+        assert_eq!(Bucket::Minute.start_of(second + Duration::seconds(42), berlin), second);
+        assert_eq!(Bucket::Day.start_of(second, berlin), utc("2026-10-24T22:00:00Z"));
+        assert_eq!(Bucket::Month.start_of(second, berlin), utc("2026-09-30T22:00:00Z"));
+    }
+
+    // Runs each check against a fresh SQLite database, and against a fresh
+    // schema on the Postgres server at DATABASE_URL if it's set.
+    macro_rules! storage_tests {
+        ($($check:ident),* $(,)?) => {
+            mod sqlite_storage {
+                $(
+                    #[tokio::test]
+                    async fn $check() {
+                        let (db, _file) = crate::db::testing::open_sqlite().await;
+                        super::$check(&db).await;
+                    }
+                )*
+            }
+
+            mod postgres_storage {
+                $(
+                    #[tokio::test]
+                    async fn $check() {
+                        let Some((db, schema)) = super::open_postgres().await else {
+                            return;
+                        };
+                        super::$check(&db).await;
+                        sqlx::query(&format!("DROP SCHEMA {} CASCADE", schema))
+                            .execute(db.pool())
+                            .await
+                            .unwrap();
+                    }
+                )*
+            }
+        };
+    }
+
+    storage_tests!(
+        intervals_are_saved_with_key_counts,
+        rollups_sum_each_bucket,
+        totals_survive_compaction,
+        outbox_is_kept_per_sink,
+        backfill_is_queued_with_its_progress,
+        sync_gaps_follow_sync_turning_off_and_on,
+    );
+
+    async fn open_postgres() -> Option<(PostgresStorage, String)> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        let schema = format!("kweeb_test_{}", Uuid::new_v4().simple());
+        let pool = sqlx::PgPool::connect(&url).await.unwrap();
+        sqlx::query(&format!("CREATE SCHEMA {}", schema)).execute(&pool).await.unwrap();
+        pool.close().await;
+
+        Some((PostgresStorage::open(&schema_url(&schema), chrono_tz::UTC).await.unwrap(), schema))
+    }
+
+    // DATABASE_URL with `schema` first on the search path.
+    fn schema_url(schema: &str) -> String {
+        let url = std::env::var("DATABASE_URL").unwrap();
+        let separator = if url.contains('?') { '&' } else { '?' };
+        format!("{}{}options[search_path]={}", url, separator, schema)
+    }
+
+    async fn drop_schema(schema: &str) {
+        let pool = sqlx::PgPool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
+        sqlx::query(&format!("DROP SCHEMA {} CASCADE", schema)).execute(&pool).await.unwrap();
+        pool.close().await;
+    }
+
+    const BUMP_SCHEMA_VERSION: &str =
+        "UPDATE _sqlx_migrations SET version = 1000000 WHERE version = (SELECT MAX(version) FROM _sqlx_migrations)";
+
+    fn assert_refused_as_newer<T>(result: Result<T>) {
+        let Err(e) = result else {
+            panic!("opened a database migrated by a newer version");
+        };
+        assert!(e.to_string().contains("newer than this build supports"), "{:#}", e);
+    }
+
+    #[tokio::test]
+    async fn sqlite_refuses_newer_schema() {
+        let (db, file) = testing::open_sqlite().await;
+        sqlx::query(BUMP_SCHEMA_VERSION).execute(db.pool()).await.unwrap();
+        db.pool().close().await;
+        assert_refused_as_newer(SqliteStorage::open(file.path(), chrono_tz::UTC).await);
+    }
+
+    #[tokio::test]
+    async fn postgres_refuses_newer_schema() {
+        let Some((db, schema)) = open_postgres().await else {
+            return;
+        };
+        sqlx::query(BUMP_SCHEMA_VERSION).execute(db.pool()).await.unwrap();
+        db.pool().close().await;
+        let result = PostgresStorage::open(&schema_url(&schema), chrono_tz::UTC).await;
+        drop_schema(&schema).await;
+        assert_refused_as_newer(result);
+    }
+
+    fn interval(end: &str, keypresses: i64, peak_kpm_1m: f64, keys: &[(Keycode, i64)]) -> Interval {
+        let end = utc(end);
+        Interval {
+            id: Uuid::new_v4(),
+            start: end - Duration::seconds(5),
+            end,
+            metrics: Metrics {
+                keypresses,
+                mouse_distance_in: keypresses as f64 / 4.0,
+                peak_kpm_1m,
+                key_counts: keys.iter().copied().collect(),
+                ..Default::default()
+            },
+        }
+    }
+
+    // Two intervals in the same hour of the first day and one on the next.
+    async fn save_history(db: &dyn Storage) -> Vec<Interval> {
+        let intervals = vec![
+            interval("2026-03-02T09:10:00Z", 10, 40.0, &[(Keycode::A, 6), (Keycode::B, 4)]),
+            interval("2026-03-02T09:40:00Z", 30, 90.0, &[(Keycode::A, 30)]),
+            interval("2026-03-03T10:05:00Z", 5, 20.0, &[(Keycode::B, 5)]),
+        ];
+        for interval in &intervals {
+            db.insert_metrics(interval, &[]).await.unwrap();
+        }
+        intervals
+    }
+
+    fn keys(counts: &[KeyCount]) -> Vec<(&str, i64)> {
+        counts.iter().map(|count| (count.key.as_str(), count.count)).collect()
+    }
+
+    async fn intervals_are_saved_with_key_counts(db: &dyn Storage) {
+        let intervals = save_history(db).await;
+        assert!(db.has_interval(intervals[0].id).await.unwrap());
+        assert!(!db.has_interval(Uuid::new_v4()).await.unwrap());
+
+        let rows = db
+            .get_metric_rows(utc("2026-03-02T00:00:00Z"), utc("2026-03-04T00:00:00Z"), Granularity::Raw)
+            .await
+            .unwrap();
+        let times: Vec<_> = rows.iter().map(|(time, _)| *time).collect();
+        assert_eq!(times, intervals.iter().map(|interval| interval.end).collect::<Vec<_>>());
+        assert_eq!(rows[1].1.keypresses, 30);
+        assert_eq!(rows[1].1.mouse_distance_in, 7.5);
+
+        let saved = db
+            .get_saved_intervals(utc("2026-03-02T09:30:00Z"), utc("2026-03-03T00:00:00Z"))
+            .await
+            .unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].id, Some(intervals[1].id));
+        assert_eq!(saved[0].start, Some(intervals[1].start));
+
+        // Part of an hour can only be answered from the raw intervals.
+        let top = db
+            .get_top_keys(utc("2026-03-02T09:00:00Z"), Some(utc("2026-03-02T09:30:00Z")), None)
+            .await
+            .unwrap();
+        assert_eq!(keys(&top), [("A", 6), ("B", 4)]);
+        let top = db
+            .get_top_keys(utc("2026-03-02T00:00:00Z"), Some(utc("2026-03-04T00:00:00Z")), Some(1))
+            .await
+            .unwrap();
+        assert_eq!(keys(&top), [("A", 36)]);
+    }
+
+    async fn rollups_sum_each_bucket(db: &dyn Storage) {
+        save_history(db).await;
+        let (start, end) = (utc("2026-03-02T00:00:00Z"), utc("2026-03-04T00:00:00Z"));
+
+        let hourly = db.get_metric_rows(start, end, Granularity::Hourly).await.unwrap();
+        let hours: Vec<_> = hourly.iter().map(|(time, metrics)| (*time, metrics.keypresses)).collect();
+        assert_eq!(hours, [(utc("2026-03-02T09:00:00Z"), 40), (utc("2026-03-03T10:00:00Z"), 5)]);
+        assert_eq!(hourly[0].1.mouse_distance_in, 10.0);
+        assert_eq!(hourly[0].1.peak_kpm_1m, 90.0);
+
+        let daily = db.get_metric_rows(start, end, Granularity::Daily).await.unwrap();
+        let days: Vec<_> = daily.iter().map(|(time, metrics)| (*time, metrics.keypresses)).collect();
+        assert_eq!(days, [(start, 40), (utc("2026-03-03T00:00:00Z"), 5)]);
+
+        // Whole days are read from the daily rollups, key counts included.
+        assert_eq!(db.granularity(start, Some(end)).await.unwrap(), Granularity::Daily);
+        let top = db.get_top_keys(start, Some(end), None).await.unwrap();
+        assert_eq!(keys(&top), [("A", 36), ("B", 9)]);
+
+        // So is a range that runs up to now, wherever in the day now is.
+        assert_eq!(db.granularity(start, None).await.unwrap(), Granularity::Daily);
+        let totals = db.get_range_totals(start, None).await.unwrap();
+        assert_eq!((totals.keypresses, totals.mouse_distance_in, totals.peak_kpm_1m), (45, 11.25, 90.0));
+        for granularity in [Granularity::Raw, Granularity::Hourly, Granularity::Daily] {
+            assert!(db.get_metric_sums(start, end, granularity).await.unwrap() == totals);
+        }
+        let nothing = db.get_metric_sums(end, end + Duration::days(1), Granularity::Raw).await.unwrap();
+        assert!(nothing.is_empty());
+
+        // Once the first day is compacted, only its rollups are left.
+        assert_eq!(db.compact(utc("2026-03-03T00:00:00Z")).await.unwrap(), 2);
+        assert_eq!(db.finest_granularity(start).await.unwrap(), Granularity::Hourly);
+        let totals = db.get_range_totals(start, Some(utc("2026-03-02T10:00:00Z"))).await.unwrap();
+        assert_eq!(totals.keypresses, 40);
+    }
+
+    async fn totals_survive_compaction(db: &dyn Storage) {
+        save_history(db).await;
+        let totals = db.get_total_metrics().await.unwrap();
+        assert_eq!(totals.total_keypresses, 45);
+        assert_eq!(totals.total_mouse_distance_in, 11.25);
+        assert!(db.verify_totals().await.unwrap().is_empty());
+
+        db.compact(utc("2026-03-03T00:00:00Z")).await.unwrap();
+        db.prune_hourly(utc("2026-03-04T00:00:00Z")).await.unwrap();
+        assert_eq!(db.get_total_metrics().await.unwrap().total_keypresses, 45);
+        assert!(db.verify_totals().await.unwrap().is_empty());
+    }
+
+    async fn outbox_is_kept_per_sink(db: &dyn Storage) {
+        let sinks = ["supabase".to_string(), "archive".to_string()];
+        let first = interval("2026-03-02T09:10:00Z", 10, 0.0, &[]);
+        let second = interval("2026-03-02T09:10:05Z", 20, 0.0, &[]);
+        db.insert_metrics(&first, &sinks).await.unwrap();
+        db.insert_metrics(&second, &sinks[..1]).await.unwrap();
+
+        let pending = db.pending_sync("supabase", 10).await.unwrap();
+        let ids: Vec<_> = pending.iter().map(|entry| entry.interval.id).collect();
+        assert_eq!(ids, [first.id, second.id]);
+        assert_eq!(pending[1].interval.metrics.keypresses, 20);
+        assert_eq!(db.pending_sync("supabase", 1).await.unwrap().len(), 1);
+
+        db.complete_sync(pending[0].id).await.unwrap();
+        db.record_sync_failure(pending[1].id, "timed out").await.unwrap();
+        db.record_sync_rejection(pending[1].id, "not acknowledged", false).await.unwrap();
+        let pending = db.pending_sync("supabase", 10).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!((pending[0].attempts, pending[0].rejections), (2, 1));
+
+        db.record_sync_rejection(pending[0].id, "not acknowledged", true).await.unwrap();
+        assert!(db.pending_sync("supabase", 10).await.unwrap().is_empty());
+
+        // The other sink's queue is untouched.
+        let archive = db.pending_sync("archive", 10).await.unwrap();
+        assert_eq!(archive.len(), 1);
+        assert_eq!(archive[0].interval.id, first.id);
+        assert_eq!(archive[0].attempts, 0);
+    }
+
+    async fn backfill_is_queued_with_its_progress(db: &dyn Storage) {
+        let history = [
+            interval("2026-03-01T09:10:00Z", 10, 0.0, &[]),
+            interval("2026-03-01T09:10:05Z", 20, 0.0, &[]),
+        ];
+        db.start_sync(utc("2026-03-05T00:00:00Z")).await.unwrap();
+        let gap = db.sync_gaps().await.unwrap()[0].id;
+        let until = utc("2026-03-02T00:00:00Z");
+        db.queue_backfill("supabase", &history, gap, until).await.unwrap();
+
+        let pending = db.pending_sync("supabase", 10).await.unwrap();
+        let ids: Vec<_> = pending.iter().map(|entry| entry.interval.id).collect();
+        assert_eq!(ids, [history[0].id, history[1].id]);
+        assert!(db.pending_sync("archive", 10).await.unwrap().is_empty());
+        assert_eq!(db.sync_gaps().await.unwrap()[0].backfilled_until, Some(until));
+    }
+
+    async fn sync_gaps_follow_sync_turning_off_and_on(db: &dyn Storage) {
+        let spans = |gaps: Vec<SyncGap>| -> Vec<_> {
+            gaps.into_iter().map(|gap| (gap.start, gap.end)).collect()
+        };
+
+        // Until sync first starts, all of history is unsynced anyway.
+        db.pause_sync(utc("2026-03-01T00:00:00Z")).await.unwrap();
+        assert!(db.sync_gaps().await.unwrap().is_empty());
+
+        let started = utc("2026-03-02T00:00:00Z");
+        db.start_sync(started).await.unwrap();
+        db.start_sync(utc("2026-03-03T00:00:00Z")).await.unwrap();
+        assert_eq!(spans(db.sync_gaps().await.unwrap()), [(None, Some(started))]);
+
+        let paused = utc("2026-03-04T00:00:00Z");
+        db.pause_sync(paused).await.unwrap();
+        db.pause_sync(utc("2026-03-05T00:00:00Z")).await.unwrap();
+        assert_eq!(spans(db.sync_gaps().await.unwrap()), [(None, Some(started)), (Some(paused), None)]);
+
+        let resumed = utc("2026-03-06T00:00:00Z");
+        db.start_sync(resumed).await.unwrap();
+        assert_eq!(
+            spans(db.sync_gaps().await.unwrap()),
+            [(None, Some(started)), (Some(paused), Some(resumed))]
+        );
+    }
+}
@@ -0,0 +1,34 @@
+use anyhow::{Context, Result};
+use chrono_tz::Tz;
+use sqlx::{migrate::Migrator, postgres::{PgPoolOptions, PgQueryResult}, Postgres};
+
+use super::{migrate, Dialect, SqlStorage};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+pub type PostgresStorage = SqlStorage<Postgres>;
+
+impl PostgresStorage {
+    pub async fn open(url: &str, timezone: Tz) -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .connect(url)
+            .await
+            .context("Failed to connect to database")?;
+
+        migrate(&pool, &MIGRATOR).await?;
+
+        Ok(Self { pool, timezone })
+    }
+}
+
+impl Dialect for Postgres {
+    const GREATEST: &'static str = "GREATEST";
+    // LIMIT NULL means no limit.
+    const NO_LIMIT: Option<i64> = None;
+    const HAS_MIGRATIONS_TABLE: &'static str = "SELECT to_regclass('_sqlx_migrations') IS NOT NULL";
+
+    fn rows_affected(result: &PgQueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
@@ -0,0 +1,113 @@
+use anyhow::{Context, Result};
+use chrono_tz::Tz;
+use sqlx::{migrate::Migrator, sqlite::{SqlitePool, SqliteQueryResult}, Sqlite};
+use std::path::Path;
+
+use super::{migrate, schema_version, Dialect, SqlStorage};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+// Columns of `metrics` in the first migration beyond the seven that
+// databases created before migrations have, with their declarations. The
+// migration's `CREATE TABLE IF NOT EXISTS` leaves such a table as it is.
+const LEGACY_METRICS_COLUMNS: &[(&str, &str)] = &[
+    ("scroll_up", "INTEGER NOT NULL DEFAULT 0"),
+    ("scroll_down", "INTEGER NOT NULL DEFAULT 0"),
+    ("scroll_left", "INTEGER NOT NULL DEFAULT 0"),
+    ("scroll_right", "INTEGER NOT NULL DEFAULT 0"),
+    ("scroll_vertical_precise", "REAL NOT NULL DEFAULT 0.0"),
+    ("scroll_horizontal_precise", "REAL NOT NULL DEFAULT 0.0"),
+    ("left_clicks", "INTEGER NOT NULL DEFAULT 0"),
+    ("right_clicks", "INTEGER NOT NULL DEFAULT 0"),
+    ("middle_clicks", "INTEGER NOT NULL DEFAULT 0"),
+    ("extra_clicks", "INTEGER NOT NULL DEFAULT 0"),
+    ("double_clicks", "INTEGER NOT NULL DEFAULT 0"),
+    ("triple_clicks", "INTEGER NOT NULL DEFAULT 0"),
+    ("drags", "INTEGER NOT NULL DEFAULT 0"),
+    ("drag_distance_in", "REAL NOT NULL DEFAULT 0.0"),
+    ("typing_keys", "INTEGER NOT NULL DEFAULT 0"),
+    ("typing_seconds", "REAL NOT NULL DEFAULT 0.0"),
+    ("typing_kpm", "REAL NOT NULL DEFAULT 0.0"),
+    ("typing_wpm", "REAL NOT NULL DEFAULT 0.0"),
+    ("peak_kpm_1m", "REAL NOT NULL DEFAULT 0.0"),
+    ("peak_kpm_5m", "REAL NOT NULL DEFAULT 0.0"),
+];
+
+pub type SqliteStorage = SqlStorage<Sqlite>;
+
+impl SqliteStorage {
+    pub async fn open(path: &Path, timezone: Tz) -> Result<Self> {
+        let pool = initialize_database(path).await?;
+        Ok(Self { pool, timezone })
+    }
+}
+
+impl Dialect for Sqlite {
+    const GREATEST: &'static str = "MAX";
+    // A negative LIMIT means no limit in SQLite.
+    const NO_LIMIT: Option<i64> = Some(-1);
+    const HAS_MIGRATIONS_TABLE: &'static str =
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')";
+
+    fn rows_affected(result: &SqliteQueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
+
+async fn initialize_database(db_path: &Path) -> Result<SqlitePool> {
+    if !db_path.exists() {
+        std::fs::File::create(db_path)?;
+        log::info!("Created new database file at {}", db_path.display());
+    }
+    log::debug!("Using existing database at {}", db_path.display());
+    let db_url = format!("sqlite:{}?mode=rwc", db_path.display());
+    let pool = SqlitePool::connect(&db_url)
+        .await
+        .context("Failed to connect to database")?;
+
+    if schema_version(&pool).await?.is_none() {
+        upgrade_legacy_schema(&pool).await?;
+    }
+    migrate(&pool, &MIGRATOR).await?;
+
+    Ok(pool)
+}
+
+// Databases from before migrations existed only have the original seven
+// columns of `metrics`. Add the rest so the first migration, which only
+// creates what's missing, leaves them matching new databases.
+async fn upgrade_legacy_schema(pool: &SqlitePool) -> Result<()> {
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'metrics')",
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to check for metrics table")?;
+    if !exists {
+        return Ok(());
+    }
+
+    log::info!("Upgrading database created before versioned migrations");
+    add_missing_columns(pool, "metrics", LEGACY_METRICS_COLUMNS).await
+}
+
+async fn add_missing_columns(pool: &SqlitePool, table: &str, columns: &[(&str, &str)]) -> Result<()> {
+    let existing: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info($1)")
+        .bind(table)
+        .fetch_all(pool)
+        .await
+        .with_context(|| format!("Failed to read columns of {}", table))?;
+
+    for (name, declaration) in columns {
+        if existing.iter().any(|column| column == name) {
+            continue;
+        }
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, name, declaration))
+            .execute(pool)
+            .await
+            .with_context(|| format!("Failed to add column {}.{}", table, name))?;
+        log::info!("Added column {}.{}", table, name);
+    }
+
+    Ok(())
+}
\ No newline at end of file
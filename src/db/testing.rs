@@ -0,0 +1,26 @@
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use super::SqliteStorage;
+
+/// A database file that's removed once the test is done with it, even if
+/// it fails.
+pub struct TempFile(PathBuf);
+
+impl TempFile {
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// A fresh SQLite database in UTC, in a temporary file.
+pub async fn open_sqlite() -> (SqliteStorage, TempFile) {
+    let path = std::env::temp_dir().join(format!("kweeb-test-{}.db", Uuid::new_v4()));
+    (SqliteStorage::open(&path, chrono_tz::UTC).await.unwrap(), TempFile(path))
+}
@@ -0,0 +1,22 @@
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use uuid::Uuid;
+
+/// The id this device's uploads are recorded under, created on first use.
+pub fn get_or_create_device_id() -> Result<String> {
+    let proj_dirs = ProjectDirs::from("com", "kweeb-logger", "logger")
+        .context("Failed to get project directories")?;
+    let data_dir = proj_dirs.data_dir();
+    let path = data_dir.join("device_id");
+
+    match std::fs::read_to_string(&path) {
+        Ok(id) => Ok(id),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let id = Uuid::new_v4().to_string();
+            std::fs::create_dir_all(data_dir).context("Failed to create data directory")?;
+            std::fs::write(&path, &id).context("Failed to save device id")?;
+            Ok(id)
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to read device id from {}", path.display())),
+    }
+}
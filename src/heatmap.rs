@@ -0,0 +1,421 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use device_query::Keycode;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::db::Storage;
+
+// Size of one key unit and the gap between keys, in SVG pixels.
+const UNIT: f64 = 54.0;
+const GAP: f64 = 4.0;
+const PADDING: f64 = 24.0;
+const HEADER: f64 = 40.0;
+const LEGEND: f64 = 48.0;
+
+const EMPTY_COLOR: (u8, u8, u8) = (0xe8, 0xe8, 0xe8);
+const GRADIENT: [(u8, u8, u8); 3] = [(0xff, 0xff, 0xcc), (0xfd, 0x8d, 0x3c), (0xbd, 0x00, 0x26)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Layout {
+    Ansi,
+    Iso,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Scale {
+    Linear,
+    Log,
+}
+
+/// One key cap, positioned in key units from the top-left corner.
+struct Key {
+    key: Option<Keycode>,
+    label: &'static str,
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+}
+
+fn key(key: Keycode, label: &'static str, x: f64, y: f64, w: f64) -> Key {
+    Key { key: Some(key), label, x, y, w, h: 1.0 }
+}
+
+// Keys device_query has no keycode for are still drawn, uncoloured.
+fn blank(label: &'static str, x: f64, y: f64, w: f64, h: f64) -> Key {
+    Key { key: None, label, x, y, w, h }
+}
+
+fn row(keys: &mut Vec<Key>, y: f64, mut x: f64, row: &[(Keycode, &'static str)]) -> f64 {
+    for (keycode, label) in row {
+        keys.push(key(*keycode, label, x, y, 1.0));
+        x += 1.0;
+    }
+    x
+}
+
+fn layout_keys(layout: Layout) -> Vec<Key> {
+    use Keycode::*;
+
+    let mut keys = Vec::new();
+
+    // Function row.
+    keys.push(key(Escape, "Esc", 0.0, 0.0, 1.0));
+    for (i, (keycode, label)) in [
+        (F1, "F1"), (F2, "F2"), (F3, "F3"), (F4, "F4"),
+        (F5, "F5"), (F6, "F6"), (F7, "F7"), (F8, "F8"),
+        (F9, "F9"), (F10, "F10"), (F11, "F11"), (F12, "F12"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let x = 2.0 + i as f64 + (i / 4) as f64 * 0.5;
+        keys.push(key(keycode, label, x, 0.0, 1.0));
+    }
+
+    // Number row.
+    let x = row(&mut keys, 1.5, 0.0, &[
+        (Grave, "`"), (Key1, "1"), (Key2, "2"), (Key3, "3"), (Key4, "4"), (Key5, "5"),
+        (Key6, "6"), (Key7, "7"), (Key8, "8"), (Key9, "9"), (Key0, "0"), (Minus, "-"),
+        (Equal, "="),
+    ]);
+    keys.push(key(Backspace, "Bksp", x, 1.5, 2.0));
+
+    // Top letter row.
+    keys.push(key(Tab, "Tab", 0.0, 2.5, 1.5));
+    let x = row(&mut keys, 2.5, 1.5, &[
+        (Q, "Q"), (W, "W"), (E, "E"), (R, "R"), (T, "T"), (Y, "Y"), (U, "U"), (I, "I"),
+        (O, "O"), (P, "P"), (LeftBracket, "["), (RightBracket, "]"),
+    ]);
+    if layout == Layout::Ansi {
+        keys.push(key(BackSlash, "\\", x, 2.5, 1.5));
+    }
+
+    // Home row.
+    keys.push(key(CapsLock, "Caps", 0.0, 3.5, 1.75));
+    let x = row(&mut keys, 3.5, 1.75, &[
+        (A, "A"), (S, "S"), (D, "D"), (F, "F"), (G, "G"), (H, "H"), (J, "J"), (K, "K"),
+        (L, "L"), (Semicolon, ";"), (Apostrophe, "'"),
+    ]);
+    match layout {
+        Layout::Ansi => keys.push(key(Enter, "Enter", x, 3.5, 2.25)),
+        Layout::Iso => {
+            keys.push(key(BackSlash, "#", x, 3.5, 1.0));
+            // The tall ISO Enter spans both rows; it's drawn as one
+            // rectangle over the lower part's width.
+            keys.push(Key { key: Some(Enter), label: "Enter", x: 13.75, y: 2.5, w: 1.25, h: 2.0 });
+        }
+    }
+
+    // Bottom letter row.
+    let x = match layout {
+        Layout::Ansi => {
+            keys.push(key(LShift, "Shift", 0.0, 4.5, 2.25));
+            2.25
+        }
+        Layout::Iso => {
+            keys.push(key(LShift, "Shift", 0.0, 4.5, 1.25));
+            keys.push(blank("\\", 1.25, 4.5, 1.0, 1.0));
+            2.25
+        }
+    };
+    let x = row(&mut keys, 4.5, x, &[
+        (Z, "Z"), (X, "X"), (C, "C"), (V, "V"), (B, "B"), (N, "N"), (M, "M"),
+        (Comma, ","), (Dot, "."), (Slash, "/"),
+    ]);
+    keys.push(key(RShift, "Shift", x, 4.5, 2.75));
+
+    // Modifier row.
+    keys.push(key(LControl, "Ctrl", 0.0, 5.5, 1.25));
+    keys.push(key(Meta, "Meta", 1.25, 5.5, 1.25));
+    keys.push(key(LAlt, "Alt", 2.5, 5.5, 1.25));
+    keys.push(key(Space, "Space", 3.75, 5.5, 6.25));
+    keys.push(key(RAlt, "Alt", 10.0, 5.5, 1.25));
+    // device_query has one keycode for both Meta keys, so presses of
+    // either are counted on the left one.
+    keys.push(blank("Meta", 11.25, 5.5, 1.25, 1.0));
+    keys.push(blank("Menu", 12.5, 5.5, 1.25, 1.0));
+    keys.push(key(RControl, "Ctrl", 13.75, 5.5, 1.25));
+
+    // Navigation cluster and arrows.
+    let nav = 15.25;
+    keys.push(key(Insert, "Ins", nav, 1.5, 1.0));
+    keys.push(key(Home, "Home", nav + 1.0, 1.5, 1.0));
+    keys.push(key(PageUp, "PgUp", nav + 2.0, 1.5, 1.0));
+    keys.push(key(Delete, "Del", nav, 2.5, 1.0));
+    keys.push(key(End, "End", nav + 1.0, 2.5, 1.0));
+    keys.push(key(PageDown, "PgDn", nav + 2.0, 2.5, 1.0));
+    keys.push(key(Up, "↑", nav + 1.0, 4.5, 1.0));
+    keys.push(key(Left, "←", nav, 5.5, 1.0));
+    keys.push(key(Down, "↓", nav + 1.0, 5.5, 1.0));
+    keys.push(key(Right, "→", nav + 2.0, 5.5, 1.0));
+
+    // Numpad.
+    let pad = 18.5;
+    keys.push(blank("Num", pad, 1.5, 1.0, 1.0));
+    keys.push(key(NumpadDivide, "/", pad + 1.0, 1.5, 1.0));
+    keys.push(key(NumpadMultiply, "*", pad + 2.0, 1.5, 1.0));
+    keys.push(key(NumpadSubtract, "-", pad + 3.0, 1.5, 1.0));
+    row(&mut keys, 2.5, pad, &[(Numpad7, "7"), (Numpad8, "8"), (Numpad9, "9")]);
+    keys.push(Key { key: Some(NumpadAdd), label: "+", x: pad + 3.0, y: 2.5, w: 1.0, h: 2.0 });
+    row(&mut keys, 3.5, pad, &[(Numpad4, "4"), (Numpad5, "5"), (Numpad6, "6")]);
+    row(&mut keys, 4.5, pad, &[(Numpad1, "1"), (Numpad2, "2"), (Numpad3, "3")]);
+    keys.push(blank("Ent", pad + 3.0, 4.5, 1.0, 2.0));
+    keys.push(Key { key: Some(Numpad0), label: "0", x: pad, y: 5.5, w: 2.0, h: 1.0 });
+    keys.push(blank(".", pad + 2.0, 5.5, 1.0, 1.0));
+
+    keys
+}
+
+fn intensity(count: i64, max: i64, scale: Scale) -> f64 {
+    if count <= 0 || max <= 0 {
+        return 0.0;
+    }
+    match scale {
+        Scale::Linear => count as f64 / max as f64,
+        Scale::Log => (count as f64).ln_1p() / (max as f64).ln_1p(),
+    }
+}
+
+fn color(t: f64) -> (u8, u8, u8) {
+    let t = t.clamp(0.0, 1.0) * (GRADIENT.len() - 1) as f64;
+    let i = (t.floor() as usize).min(GRADIENT.len() - 2);
+    let f = t - i as f64;
+    let (a, b) = (GRADIENT[i], GRADIENT[i + 1]);
+    let mix = |x: u8, y: u8| (x as f64 + (y as f64 - x as f64) * f).round() as u8;
+    (mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2))
+}
+
+fn hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Renders a keyboard with every key coloured by its press count.
+pub fn render_svg(counts: &HashMap<Keycode, i64>, layout: Layout, scale: Scale, title: &str) -> String {
+    let keys = layout_keys(layout);
+    let max = counts.values().copied().max().unwrap_or(0);
+
+    let columns = keys.iter().map(|k| k.x + k.w).fold(0.0, f64::max);
+    let rows = keys.iter().map(|k| k.y + k.h).fold(0.0, f64::max);
+    let width = PADDING * 2.0 + columns * UNIT;
+    let height = PADDING * 2.0 + HEADER + rows * UNIT + LEGEND;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.0}" height="{h:.0}" viewBox="0 0 {w:.0} {h:.0}" font-family="Helvetica, Arial, sans-serif">"#,
+        w = width,
+        h = height,
+    );
+    let _ = writeln!(svg, r##"<rect width="100%" height="100%" fill="#ffffff"/>"##);
+    let _ = writeln!(
+        svg,
+        r##"<text x="{}" y="{}" font-size="18" fill="#333333">{}</text>"##,
+        PADDING,
+        PADDING + 18.0,
+        escape(title),
+    );
+
+    for k in &keys {
+        let count = k.key.and_then(|key| counts.get(&key)).copied().unwrap_or(0);
+        let t = intensity(count, max, scale);
+        let fill = if count > 0 { color(t) } else { EMPTY_COLOR };
+        let text = if t > 0.6 { "#ffffff" } else { "#333333" };
+
+        let x = PADDING + k.x * UNIT + GAP / 2.0;
+        let y = PADDING + HEADER + k.y * UNIT + GAP / 2.0;
+        let w = k.w * UNIT - GAP;
+        let h = k.h * UNIT - GAP;
+
+        let _ = writeln!(svg, "<g>");
+        if let Some(key) = k.key {
+            let _ = writeln!(svg, "<title>{}: {}</title>", key, count);
+        }
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" rx="5" fill="{}" stroke="#bbbbbb"/>"##,
+            x, y, w, h, hex(fill),
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{:.1}" y="{:.1}" font-size="13" text-anchor="middle" fill="{}">{}</text>"#,
+            x + w / 2.0,
+            y + h / 2.0,
+            text,
+            escape(k.label),
+        );
+        if count > 0 {
+            let _ = writeln!(
+                svg,
+                r#"<text x="{:.1}" y="{:.1}" font-size="9" text-anchor="middle" fill="{}">{}</text>"#,
+                x + w / 2.0,
+                y + h / 2.0 + 14.0,
+                text,
+                count,
+            );
+        }
+        let _ = writeln!(svg, "</g>");
+    }
+
+    // Legend: the gradient from zero to the busiest key.
+    let legend_y = PADDING + HEADER + rows * UNIT + 16.0;
+    let legend_w = 240.0;
+    let _ = writeln!(svg, r#"<defs><linearGradient id="scale">"#);
+    for (i, stop) in GRADIENT.iter().enumerate() {
+        let _ = writeln!(
+            svg,
+            r#"<stop offset="{}%" stop-color="{}"/>"#,
+            i * 100 / (GRADIENT.len() - 1),
+            hex(*stop),
+        );
+    }
+    let _ = writeln!(svg, "</linearGradient></defs>");
+    let _ = writeln!(
+        svg,
+        r##"<rect x="{}" y="{:.1}" width="{}" height="12" fill="url(#scale)" stroke="#bbbbbb"/>"##,
+        PADDING, legend_y, legend_w,
+    );
+    let _ = writeln!(
+        svg,
+        r##"<text x="{}" y="{:.1}" font-size="11" fill="#333333">1</text>"##,
+        PADDING,
+        legend_y + 26.0,
+    );
+    let _ = writeln!(
+        svg,
+        r##"<text x="{}" y="{:.1}" font-size="11" text-anchor="end" fill="#333333">{} ({} scale)</text>"##,
+        PADDING + legend_w,
+        legend_y + 26.0,
+        max,
+        match scale {
+            Scale::Linear => "linear",
+            Scale::Log => "log",
+        },
+    );
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Renders the heatmap for key presses between `start` and `end`, or now
+/// if there's no `end`.
+pub async fn render_from_db(
+    db: &dyn Storage,
+    start: DateTime<Utc>,
+    end: Option<DateTime<Utc>>,
+    layout: Layout,
+    scale: Scale,
+) -> Result<String> {
+    let mut counts = HashMap::new();
+    for key_count in db.get_key_counts(start, end).await? {
+        match Keycode::from_str(&key_count.key) {
+            Ok(key) => {
+                *counts.entry(key).or_insert(0) += key_count.count;
+            }
+            Err(_) => log::warn!("Skipping unknown keycode {}", key_count.key),
+        }
+    }
+
+    let end_label = end.unwrap_or_else(Utc::now).with_timezone(&db.timezone()).format("%Y-%m-%d %H:%M");
+    let title = if start <= DateTime::UNIX_EPOCH {
+        format!("Key presses up to {}", end_label)
+    } else {
+        format!(
+            "Key presses {} – {}",
+            start.with_timezone(&db.timezone()).format("%Y-%m-%d %H:%M"),
+            end_label,
+        )
+    };
+    Ok(render_svg(&counts, layout, scale, &title))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(keys: &[Key], keycode: Keycode) -> Vec<&Key> {
+        keys.iter().filter(|k| k.key == Some(keycode)).collect()
+    }
+
+    #[test]
+    fn every_key_is_drawn_once() {
+        for layout in [Layout::Ansi, Layout::Iso] {
+            let keys = layout_keys(layout);
+            for k in &keys {
+                if let Some(keycode) = k.key {
+                    assert_eq!(find(&keys, keycode).len(), 1, "{:?} in {:?}", keycode, layout);
+                }
+            }
+            assert_eq!(keys.iter().filter(|k| k.label == "Meta").count(), 2);
+        }
+    }
+
+    #[test]
+    fn layouts_place_backslash_and_enter() {
+        let ansi = layout_keys(Layout::Ansi);
+        let backslash = find(&ansi, Keycode::BackSlash)[0];
+        assert_eq!((backslash.x, backslash.y, backslash.w), (13.5, 2.5, 1.5));
+        let enter = find(&ansi, Keycode::Enter)[0];
+        assert_eq!((enter.y, enter.w, enter.h), (3.5, 2.25, 1.0));
+
+        let iso = layout_keys(Layout::Iso);
+        let backslash = find(&iso, Keycode::BackSlash)[0];
+        assert_eq!((backslash.label, backslash.x, backslash.y), ("#", 12.75, 3.5));
+        let enter = find(&iso, Keycode::Enter)[0];
+        assert_eq!((enter.y, enter.h), (2.5, 2.0));
+    }
+
+    #[test]
+    fn log_scale_lifts_rarely_pressed_keys() {
+        assert_eq!(intensity(0, 100, Scale::Linear), 0.0);
+        assert_eq!(intensity(0, 100, Scale::Log), 0.0);
+        assert_eq!(intensity(5, 0, Scale::Linear), 0.0);
+        assert_eq!(intensity(100, 100, Scale::Linear), 1.0);
+        assert_eq!(intensity(100, 100, Scale::Log), 1.0);
+
+        assert!((intensity(10, 100, Scale::Linear) - 0.1).abs() < 1e-9);
+        let log = intensity(10, 100, Scale::Log);
+        assert!((log - 11f64.ln() / 101f64.ln()).abs() < 1e-9);
+        assert!(log > 0.5);
+    }
+
+    #[test]
+    fn colors_follow_the_gradient() {
+        assert_eq!(color(0.0), GRADIENT[0]);
+        assert_eq!(color(0.5), GRADIENT[1]);
+        assert_eq!(color(1.0), GRADIENT[2]);
+        assert_eq!(color(2.0), GRADIENT[2]);
+        assert_eq!(hex(GRADIENT[2]), "#bd0026");
+    }
+
+    #[test]
+    fn svg_colours_pressed_keys() {
+        let counts = HashMap::from([(Keycode::A, 40), (Keycode::Z, 10), (Keycode::Meta, 7)]);
+        let svg = render_svg(&counts, Layout::Ansi, Scale::Linear, "Keys <today>");
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains(">Keys &lt;today&gt;</text>"));
+        assert!(svg.contains("<title>A: 40</title>"));
+        assert!(svg.contains("<title>Z: 10</title>"));
+        assert!(svg.contains("<title>S: 0</title>"));
+        // The busiest key gets the top of the gradient.
+        assert!(svg.contains(r##"fill="#bd0026""##));
+        assert!(svg.contains(">40 (linear scale)</text>"));
+
+        assert_eq!(svg.matches("<title>Meta: 7</title>").count(), 1);
+        let counts_drawn = svg.lines().filter(|line| line.contains(r#"font-size="9""#));
+        assert_eq!(counts_drawn.filter(|line| line.ends_with(">7</text>")).count(), 1);
+    }
+}
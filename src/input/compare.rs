@@ -0,0 +1,80 @@
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::poller::DeviceQueryPoller;
+use super::{InputEvent, InputEventKind, InputSource};
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CaptureCounts {
+    pub key_presses: u64,
+    pub button_presses: u64,
+}
+
+impl CaptureCounts {
+    pub fn record(&mut self, event: &InputEvent) {
+        match event.kind {
+            InputEventKind::KeyDown(_) => self.key_presses += 1,
+            InputEventKind::ButtonDown(_) => self.button_presses += 1,
+            _ => {}
+        }
+    }
+}
+
+/// Runs the 100 ms poller next to the event-driven source and periodically
+/// logs how many presses the poller failed to see.
+pub struct PollerComparison {
+    exact: CaptureCounts,
+    polled: Arc<Mutex<CaptureCounts>>,
+    last_report: Instant,
+}
+
+impl PollerComparison {
+    pub fn start() -> Self {
+        let polled = Arc::new(Mutex::new(CaptureCounts::default()));
+        let poller_counts = Arc::clone(&polled);
+
+        std::thread::spawn(move || {
+            let mut poller = DeviceQueryPoller::new();
+            while let Ok(Some(event)) = poller.next_event() {
+                poller_counts.lock().record(&event);
+            }
+        });
+
+        Self {
+            exact: CaptureCounts::default(),
+            polled,
+            last_report: Instant::now(),
+        }
+    }
+
+    pub fn record(&mut self, event: &InputEvent) {
+        self.exact.record(event);
+    }
+
+    pub fn missed(&self) -> CaptureCounts {
+        let polled = *self.polled.lock();
+        CaptureCounts {
+            key_presses: self.exact.key_presses.saturating_sub(polled.key_presses),
+            button_presses: self.exact.button_presses.saturating_sub(polled.button_presses),
+        }
+    }
+
+    pub fn report_if_due(&mut self) {
+        if self.last_report.elapsed() < REPORT_INTERVAL {
+            return;
+        }
+        self.last_report = Instant::now();
+
+        let missed = self.missed();
+        log::info!(
+            "Poller comparison: {} of {} key presses and {} of {} clicks would have been missed by polling",
+            missed.key_presses,
+            self.exact.key_presses,
+            missed.button_presses,
+            self.exact.button_presses,
+        );
+    }
+}
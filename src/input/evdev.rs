@@ -0,0 +1,529 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use device_query::Keycode;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+use super::{InputEvent, InputEventKind, InputSource, MouseButton};
+
+// Size of `struct input_event` on 64-bit Linux: timeval (2 x i64), type,
+// code and value.
+const EVENT_SIZE: usize = 24;
+
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+
+const SYN_REPORT: u16 = 0x00;
+
+const REL_X: u16 = 0x00;
+const REL_Y: u16 = 0x01;
+const REL_HWHEEL: u16 = 0x06;
+const REL_WHEEL: u16 = 0x08;
+const REL_WHEEL_HI_RES: u16 = 0x0b;
+const REL_HWHEEL_HI_RES: u16 = 0x0c;
+
+// High-resolution wheel events report 120 units per notch.
+const HI_RES_UNITS_PER_STEP: f64 = 120.0;
+
+const BTN_LEFT: u16 = 0x110;
+const BTN_RIGHT: u16 = 0x111;
+const BTN_MIDDLE: u16 = 0x112;
+const BTN_TASK: u16 = 0x117;
+
+const KEY_RELEASED: i32 = 0;
+const KEY_PRESSED: i32 = 1;
+
+/// One raw `struct input_event` as read from `/dev/input/event*` or from a
+/// recorded dump of one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawEvent {
+    pub sec: i64,
+    pub usec: i64,
+    pub type_: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl RawEvent {
+    pub fn from_bytes(buf: &[u8; EVENT_SIZE]) -> Self {
+        Self {
+            sec: i64::from_ne_bytes(buf[0..8].try_into().unwrap()),
+            usec: i64::from_ne_bytes(buf[8..16].try_into().unwrap()),
+            type_: u16::from_ne_bytes(buf[16..18].try_into().unwrap()),
+            code: u16::from_ne_bytes(buf[18..20].try_into().unwrap()),
+            value: i32::from_ne_bytes(buf[20..24].try_into().unwrap()),
+        }
+    }
+
+    fn time(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.sec, (self.usec * 1000) as u32).unwrap_or_else(Utc::now)
+    }
+}
+
+/// Reads the next raw event, returning `None` at a clean end of stream.
+pub fn read_raw_event(reader: &mut impl Read) -> Result<Option<RawEvent>> {
+    let mut buf = [0u8; EVENT_SIZE];
+    let mut filled = 0;
+    while filled < EVENT_SIZE {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => bail!("Truncated evdev event ({} of {} bytes)", filled, EVENT_SIZE),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e).context("Failed to read evdev event"),
+        }
+    }
+    Ok(Some(RawEvent::from_bytes(&buf)))
+}
+
+/// Turns the raw event stream of a single device into input events.
+///
+/// Relative motion and wheel movement are collected until the next
+/// `SYN_REPORT`, so a diagonal movement becomes one pointer event and a
+/// wheel that reports both notches and high-resolution units becomes one
+/// scroll event.
+#[derive(Default)]
+pub struct EvdevDecoder {
+    rel_x: i32,
+    rel_y: i32,
+    wheel: i32,
+    hwheel: i32,
+    wheel_hi_res: Option<i32>,
+    hwheel_hi_res: Option<i32>,
+}
+
+impl EvdevDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn decode(&mut self, raw: &RawEvent, out: &mut Vec<InputEvent>) {
+        let kind = match (raw.type_, raw.code) {
+            (EV_KEY, code) if (BTN_LEFT..=BTN_TASK).contains(&code) => {
+                let button = match code {
+                    BTN_LEFT => MouseButton::Left,
+                    BTN_RIGHT => MouseButton::Right,
+                    BTN_MIDDLE => MouseButton::Middle,
+                    other => MouseButton::Other((other - BTN_LEFT + 1) as u8),
+                };
+                match raw.value {
+                    KEY_PRESSED => InputEventKind::ButtonDown(button),
+                    KEY_RELEASED => InputEventKind::ButtonUp(button),
+                    _ => return,
+                }
+            }
+            (EV_KEY, code) => {
+                let Some(key) = keycode_from_linux(code) else {
+                    return;
+                };
+                // Autorepeat (value 2) is not a new press.
+                match raw.value {
+                    KEY_PRESSED => InputEventKind::KeyDown(key),
+                    KEY_RELEASED => InputEventKind::KeyUp(key),
+                    _ => return,
+                }
+            }
+            (EV_REL, REL_X) => {
+                self.rel_x += raw.value;
+                return;
+            }
+            (EV_REL, REL_Y) => {
+                self.rel_y += raw.value;
+                return;
+            }
+            (EV_REL, REL_WHEEL) => {
+                self.wheel += raw.value;
+                return;
+            }
+            (EV_REL, REL_HWHEEL) => {
+                self.hwheel += raw.value;
+                return;
+            }
+            (EV_REL, REL_WHEEL_HI_RES) => {
+                *self.wheel_hi_res.get_or_insert(0) += raw.value;
+                return;
+            }
+            (EV_REL, REL_HWHEEL_HI_RES) => {
+                *self.hwheel_hi_res.get_or_insert(0) += raw.value;
+                return;
+            }
+            (EV_SYN, SYN_REPORT) => {
+                self.flush(raw, out);
+                return;
+            }
+            _ => return,
+        };
+
+        out.push(InputEvent {
+            time: raw.time(),
+            kind,
+        });
+    }
+
+    fn flush(&mut self, raw: &RawEvent, out: &mut Vec<InputEvent>) {
+        if self.rel_x != 0 || self.rel_y != 0 {
+            out.push(InputEvent {
+                time: raw.time(),
+                kind: InputEventKind::PointerDelta {
+                    dx: self.rel_x,
+                    dy: self.rel_y,
+                },
+            });
+        }
+
+        let precise_x = self.hwheel_hi_res
+            .map(|units| units as f64 / HI_RES_UNITS_PER_STEP)
+            .unwrap_or(self.hwheel as f64);
+        let precise_y = self.wheel_hi_res
+            .map(|units| units as f64 / HI_RES_UNITS_PER_STEP)
+            .unwrap_or(self.wheel as f64);
+        if precise_x != 0.0 || precise_y != 0.0 || self.wheel != 0 || self.hwheel != 0 {
+            out.push(InputEvent {
+                time: raw.time(),
+                kind: InputEventKind::Scroll {
+                    delta_x: self.hwheel,
+                    delta_y: self.wheel,
+                    precise_x,
+                    precise_y,
+                },
+            });
+        }
+
+        *self = Self::default();
+    }
+}
+
+/// Decodes every event in a recorded evdev dump (the raw bytes of
+/// `cat /dev/input/eventN`).
+#[cfg(test)]
+pub fn decode_dump(reader: &mut impl Read) -> Result<Vec<InputEvent>> {
+    let mut decoder = EvdevDecoder::new();
+    let mut events = Vec::new();
+    while let Some(raw) = read_raw_event(reader)? {
+        decoder.decode(&raw, &mut events);
+    }
+    Ok(events)
+}
+
+/// Reads one or more evdev devices, each on its own thread.
+///
+/// Paths may also point at recorded dumps, in which case the source
+/// finishes once every dump has been replayed.
+pub struct EvdevSource {
+    events: mpsc::Receiver<InputEvent>,
+}
+
+impl EvdevSource {
+    pub fn open(devices: &[String]) -> Result<Self> {
+        let paths = if devices.is_empty() {
+            discover_devices()?
+        } else {
+            devices.iter().map(PathBuf::from).collect()
+        };
+
+        let (tx, rx) = mpsc::channel();
+        let mut opened = 0;
+        for path in paths {
+            let file = match File::open(&path) {
+                Ok(file) => file,
+                Err(e) => {
+                    log::warn!("Skipping evdev device {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            log::info!("Reading evdev device {}", path.display());
+            let tx = tx.clone();
+            std::thread::spawn(move || read_device(path, file, tx));
+            opened += 1;
+        }
+
+        if opened == 0 {
+            bail!("No readable evdev devices (is the user in the `input` group?)");
+        }
+
+        Ok(Self { events: rx })
+    }
+}
+
+impl InputSource for EvdevSource {
+    fn name(&self) -> &'static str {
+        "evdev"
+    }
+
+    fn next_event(&mut self) -> Result<Option<InputEvent>> {
+        Ok(self.events.recv().ok())
+    }
+}
+
+fn read_device(path: PathBuf, mut file: File, tx: mpsc::Sender<InputEvent>) {
+    let mut decoder = EvdevDecoder::new();
+    let mut events = Vec::new();
+    loop {
+        match read_raw_event(&mut file) {
+            Ok(Some(raw)) => {
+                decoder.decode(&raw, &mut events);
+                for event in events.drain(..) {
+                    if tx.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+            Ok(None) => return,
+            Err(e) => {
+                log::error!("Stopped reading {}: {}", path.display(), e);
+                return;
+            }
+        }
+    }
+}
+
+/// Prefers the keyboard and mouse nodes udev links under `by-id`/`by-path`,
+/// falling back to every `/dev/input/event*` node.
+fn discover_devices() -> Result<Vec<PathBuf>> {
+    let mut devices = Vec::new();
+    for dir in ["/dev/input/by-id", "/dev/input/by-path"] {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.ends_with("-event-kbd") || name.ends_with("-event-mouse") {
+                if let Ok(target) = std::fs::canonicalize(entry.path()) {
+                    if !devices.contains(&target) {
+                        devices.push(target);
+                    }
+                }
+            }
+        }
+    }
+
+    if devices.is_empty() {
+        for entry in std::fs::read_dir("/dev/input").context("Failed to list /dev/input")?.flatten() {
+            if entry.file_name().to_string_lossy().starts_with("event") {
+                devices.push(entry.path());
+            }
+        }
+    }
+
+    devices.sort();
+    Ok(devices)
+}
+
+fn keycode_from_linux(code: u16) -> Option<Keycode> {
+    let key = match code {
+        1 => Keycode::Escape,
+        2 => Keycode::Key1,
+        3 => Keycode::Key2,
+        4 => Keycode::Key3,
+        5 => Keycode::Key4,
+        6 => Keycode::Key5,
+        7 => Keycode::Key6,
+        8 => Keycode::Key7,
+        9 => Keycode::Key8,
+        10 => Keycode::Key9,
+        11 => Keycode::Key0,
+        12 => Keycode::Minus,
+        13 => Keycode::Equal,
+        14 => Keycode::Backspace,
+        15 => Keycode::Tab,
+        16 => Keycode::Q,
+        17 => Keycode::W,
+        18 => Keycode::E,
+        19 => Keycode::R,
+        20 => Keycode::T,
+        21 => Keycode::Y,
+        22 => Keycode::U,
+        23 => Keycode::I,
+        24 => Keycode::O,
+        25 => Keycode::P,
+        26 => Keycode::LeftBracket,
+        27 => Keycode::RightBracket,
+        28 => Keycode::Enter,
+        29 => Keycode::LControl,
+        30 => Keycode::A,
+        31 => Keycode::S,
+        32 => Keycode::D,
+        33 => Keycode::F,
+        34 => Keycode::G,
+        35 => Keycode::H,
+        36 => Keycode::J,
+        37 => Keycode::K,
+        38 => Keycode::L,
+        39 => Keycode::Semicolon,
+        40 => Keycode::Apostrophe,
+        41 => Keycode::Grave,
+        42 => Keycode::LShift,
+        43 => Keycode::BackSlash,
+        44 => Keycode::Z,
+        45 => Keycode::X,
+        46 => Keycode::C,
+        47 => Keycode::V,
+        48 => Keycode::B,
+        49 => Keycode::N,
+        50 => Keycode::M,
+        51 => Keycode::Comma,
+        52 => Keycode::Dot,
+        53 => Keycode::Slash,
+        54 => Keycode::RShift,
+        55 => Keycode::NumpadMultiply,
+        56 => Keycode::LAlt,
+        57 => Keycode::Space,
+        58 => Keycode::CapsLock,
+        59 => Keycode::F1,
+        60 => Keycode::F2,
+        61 => Keycode::F3,
+        62 => Keycode::F4,
+        63 => Keycode::F5,
+        64 => Keycode::F6,
+        65 => Keycode::F7,
+        66 => Keycode::F8,
+        67 => Keycode::F9,
+        68 => Keycode::F10,
+        71 => Keycode::Numpad7,
+        72 => Keycode::Numpad8,
+        73 => Keycode::Numpad9,
+        74 => Keycode::NumpadSubtract,
+        75 => Keycode::Numpad4,
+        76 => Keycode::Numpad5,
+        77 => Keycode::Numpad6,
+        78 => Keycode::NumpadAdd,
+        79 => Keycode::Numpad1,
+        80 => Keycode::Numpad2,
+        81 => Keycode::Numpad3,
+        82 => Keycode::Numpad0,
+        87 => Keycode::F11,
+        88 => Keycode::F12,
+        96 => Keycode::Enter,
+        97 => Keycode::RControl,
+        98 => Keycode::NumpadDivide,
+        100 => Keycode::RAlt,
+        102 => Keycode::Home,
+        103 => Keycode::Up,
+        104 => Keycode::PageUp,
+        105 => Keycode::Left,
+        106 => Keycode::Right,
+        107 => Keycode::End,
+        108 => Keycode::Down,
+        109 => Keycode::PageDown,
+        110 => Keycode::Insert,
+        111 => Keycode::Delete,
+        125 | 126 => Keycode::Meta,
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_A: u16 = 30;
+    const KEY_AUTOREPEAT: i32 = 2;
+    const BTN_SIDE: u16 = 0x113;
+
+    // Writes events the way the kernel does, 10 ms apart.
+    fn dump(events: &[(u16, u16, i32)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (i, &(type_, code, value)) in events.iter().enumerate() {
+            bytes.extend_from_slice(&1_760_000_000i64.to_ne_bytes());
+            bytes.extend_from_slice(&(i as i64 * 10_000).to_ne_bytes());
+            bytes.extend_from_slice(&type_.to_ne_bytes());
+            bytes.extend_from_slice(&code.to_ne_bytes());
+            bytes.extend_from_slice(&value.to_ne_bytes());
+        }
+        bytes
+    }
+
+    fn decode(events: &[(u16, u16, i32)]) -> Vec<InputEventKind> {
+        decode_dump(&mut dump(events).as_slice())
+            .unwrap()
+            .into_iter()
+            .map(|event| event.kind)
+            .collect()
+    }
+
+    #[test]
+    fn motion_is_coalesced_until_syn_report() {
+        let kinds = decode(&[
+            (EV_REL, REL_X, 3),
+            (EV_REL, REL_Y, 4),
+            (EV_REL, REL_X, 2),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_REL, REL_Y, -1),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_REL, REL_X, 7),
+        ]);
+        assert_eq!(kinds, vec![
+            InputEventKind::PointerDelta { dx: 5, dy: 4 },
+            InputEventKind::PointerDelta { dx: 0, dy: -1 },
+        ]);
+    }
+
+    #[test]
+    fn autorepeat_is_not_a_press() {
+        let kinds = decode(&[
+            (EV_KEY, KEY_A, KEY_PRESSED),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_KEY, KEY_A, KEY_AUTOREPEAT),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_KEY, KEY_A, KEY_AUTOREPEAT),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_KEY, KEY_A, KEY_RELEASED),
+            (EV_SYN, SYN_REPORT, 0),
+        ]);
+        assert_eq!(kinds, vec![
+            InputEventKind::KeyDown(Keycode::A),
+            InputEventKind::KeyUp(Keycode::A),
+        ]);
+    }
+
+    #[test]
+    fn hi_res_wheel_takes_precedence() {
+        let kinds = decode(&[
+            // A notch reported both ways is one step, not two.
+            (EV_REL, REL_WHEEL, 1),
+            (EV_REL, REL_WHEEL_HI_RES, 120),
+            (EV_SYN, SYN_REPORT, 0),
+            // Half a notch only shows up in high resolution.
+            (EV_REL, REL_HWHEEL_HI_RES, -60),
+            (EV_SYN, SYN_REPORT, 0),
+            // A wheel without high resolution falls back to notches.
+            (EV_REL, REL_WHEEL, -2),
+            (EV_SYN, SYN_REPORT, 0),
+        ]);
+        assert_eq!(kinds, vec![
+            InputEventKind::Scroll { delta_x: 0, delta_y: 1, precise_x: 0.0, precise_y: 1.0 },
+            InputEventKind::Scroll { delta_x: 0, delta_y: 0, precise_x: -0.5, precise_y: 0.0 },
+            InputEventKind::Scroll { delta_x: 0, delta_y: -2, precise_x: 0.0, precise_y: -2.0 },
+        ]);
+    }
+
+    #[test]
+    fn buttons_are_mapped() {
+        let kinds = decode(&[
+            (EV_KEY, BTN_LEFT, KEY_PRESSED),
+            (EV_KEY, BTN_LEFT, KEY_RELEASED),
+            (EV_KEY, BTN_RIGHT, KEY_PRESSED),
+            (EV_KEY, BTN_MIDDLE, KEY_PRESSED),
+            (EV_KEY, BTN_SIDE, KEY_PRESSED),
+            (EV_SYN, SYN_REPORT, 0),
+        ]);
+        assert_eq!(kinds, vec![
+            InputEventKind::ButtonDown(MouseButton::Left),
+            InputEventKind::ButtonUp(MouseButton::Left),
+            InputEventKind::ButtonDown(MouseButton::Right),
+            InputEventKind::ButtonDown(MouseButton::Middle),
+            InputEventKind::ButtonDown(MouseButton::Other(4)),
+        ]);
+    }
+
+    #[test]
+    fn truncated_dump_is_an_error() {
+        let mut bytes = dump(&[(EV_KEY, KEY_A, KEY_PRESSED)]);
+        bytes.truncate(EVENT_SIZE - 4);
+        assert!(decode_dump(&mut bytes.as_slice()).is_err());
+    }
+}
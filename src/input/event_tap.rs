@@ -0,0 +1,307 @@
+use anyhow::{anyhow, Result};
+use core_foundation::base::TCFType;
+use core_foundation::mach_port::CFMachPortRef;
+use core_foundation::runloop::{kCFRunLoopCommonModes, CFRunLoop};
+use core_graphics::event::{
+    CGEvent, CGEventField, CGEventTap, CGEventTapLocation, CGEventTapOptions, CGEventTapPlacement,
+    CGEventType, EventField,
+};
+use device_query::Keycode;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::sync::mpsc;
+
+use super::{InputEvent, InputEventKind, InputSource, MouseButton};
+
+// kCGScrollWheelEventMomentumPhase, which core-graphics doesn't define.
+// Non-zero while a trackpad scroll coasts on after the fingers lift.
+const SCROLL_WHEEL_EVENT_MOMENTUM_PHASE: CGEventField = 123;
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGEventTapEnable(tap: CFMachPortRef, enable: bool);
+}
+
+/// Receives every key, button, pointer and scroll event from a listen-only
+/// Quartz event tap, so nothing is lost between samples.
+///
+/// Needs the Input Monitoring permission; `open` fails without it.
+pub struct EventTapSource {
+    events: mpsc::Receiver<InputEvent>,
+}
+
+impl EventTapSource {
+    pub fn open() -> Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        std::thread::spawn(move || run_tap(tx, ready_tx));
+
+        ready_rx
+            .recv()
+            .map_err(|_| anyhow!("Event tap thread exited during startup"))??;
+
+        Ok(Self { events: rx })
+    }
+}
+
+impl InputSource for EventTapSource {
+    fn name(&self) -> &'static str {
+        "event tap"
+    }
+
+    fn next_event(&mut self) -> Result<Option<InputEvent>> {
+        Ok(self.events.recv().ok())
+    }
+}
+
+fn run_tap(tx: mpsc::Sender<InputEvent>, ready: mpsc::Sender<Result<()>>) {
+    let held_modifiers = RefCell::new(HashSet::new());
+    let port: Rc<Cell<Option<CFMachPortRef>>> = Rc::new(Cell::new(None));
+    let callback_port = Rc::clone(&port);
+
+    let tap = CGEventTap::new(
+        CGEventTapLocation::Session,
+        CGEventTapPlacement::TailAppendEventTap,
+        CGEventTapOptions::ListenOnly,
+        vec![
+            CGEventType::KeyDown,
+            CGEventType::KeyUp,
+            CGEventType::FlagsChanged,
+            CGEventType::LeftMouseDown,
+            CGEventType::LeftMouseUp,
+            CGEventType::RightMouseDown,
+            CGEventType::RightMouseUp,
+            CGEventType::OtherMouseDown,
+            CGEventType::OtherMouseUp,
+            CGEventType::MouseMoved,
+            CGEventType::LeftMouseDragged,
+            CGEventType::RightMouseDragged,
+            CGEventType::OtherMouseDragged,
+            CGEventType::ScrollWheel,
+        ],
+        move |_proxy, event_type, event| {
+            match event_type {
+                CGEventType::TapDisabledByTimeout | CGEventType::TapDisabledByUserInput => {
+                    log::warn!("Event tap was disabled by the system, re-enabling");
+                    if let Some(port) = callback_port.get() {
+                        unsafe { CGEventTapEnable(port, true) };
+                    }
+                }
+                _ => {
+                    if let Some(kind) = translate(event_type, event, &mut held_modifiers.borrow_mut()) {
+                        let _ = tx.send(InputEvent::now(kind));
+                    }
+                }
+            }
+            None
+        },
+    );
+
+    let tap = match tap {
+        Ok(tap) => tap,
+        Err(()) => {
+            let _ = ready.send(Err(anyhow!(
+                "Failed to create event tap (is Input Monitoring allowed?)"
+            )));
+            return;
+        }
+    };
+
+    let source = match tap.mach_port.create_runloop_source(0) {
+        Ok(source) => source,
+        Err(()) => {
+            let _ = ready.send(Err(anyhow!("Failed to create event tap run loop source")));
+            return;
+        }
+    };
+
+    port.set(Some(tap.mach_port.as_concrete_TypeRef()));
+    unsafe {
+        CFRunLoop::get_current().add_source(&source, kCFRunLoopCommonModes);
+    }
+    tap.enable();
+    let _ = ready.send(Ok(()));
+
+    CFRunLoop::run_current();
+}
+
+fn translate(
+    event_type: CGEventType,
+    event: &CGEvent,
+    held_modifiers: &mut HashSet<i64>,
+) -> Option<InputEventKind> {
+    let kind = match event_type {
+        CGEventType::KeyDown => {
+            if event.get_integer_value_field(EventField::KEYBOARD_EVENT_AUTOREPEAT) != 0 {
+                return None;
+            }
+            InputEventKind::KeyDown(keycode(event)?)
+        }
+        CGEventType::KeyUp => InputEventKind::KeyUp(keycode(event)?),
+        // Modifier keys only report a change of flags, so track which ones
+        // are held to tell presses from releases.
+        CGEventType::FlagsChanged => {
+            let code = event.get_integer_value_field(EventField::KEYBOARD_EVENT_KEYCODE);
+            let key = keycode(event)?;
+            // Caps Lock reports a single change per press.
+            if key == Keycode::CapsLock {
+                return Some(InputEventKind::KeyDown(key));
+            }
+            if held_modifiers.remove(&code) {
+                InputEventKind::KeyUp(key)
+            } else {
+                held_modifiers.insert(code);
+                InputEventKind::KeyDown(key)
+            }
+        }
+        CGEventType::LeftMouseDown => InputEventKind::ButtonDown(MouseButton::Left),
+        CGEventType::LeftMouseUp => InputEventKind::ButtonUp(MouseButton::Left),
+        CGEventType::RightMouseDown => InputEventKind::ButtonDown(MouseButton::Right),
+        CGEventType::RightMouseUp => InputEventKind::ButtonUp(MouseButton::Right),
+        CGEventType::OtherMouseDown => InputEventKind::ButtonDown(button_number(event)),
+        CGEventType::OtherMouseUp => InputEventKind::ButtonUp(button_number(event)),
+        CGEventType::MouseMoved
+        | CGEventType::LeftMouseDragged
+        | CGEventType::RightMouseDragged
+        | CGEventType::OtherMouseDragged => {
+            let location = event.location();
+            InputEventKind::PointerMoved {
+                x: location.x as i32,
+                y: location.y as i32,
+            }
+        }
+        // Inertia isn't the user scrolling.
+        CGEventType::ScrollWheel
+            if event.get_integer_value_field(SCROLL_WHEEL_EVENT_MOMENTUM_PHASE) != 0 =>
+        {
+            return None;
+        }
+        // Axis 2 is positive when scrolling left, so flip it to match the
+        // other sources.
+        CGEventType::ScrollWheel => InputEventKind::Scroll {
+            delta_x: -event.get_integer_value_field(EventField::SCROLL_WHEEL_EVENT_DELTA_AXIS_2) as i32,
+            delta_y: event.get_integer_value_field(EventField::SCROLL_WHEEL_EVENT_DELTA_AXIS_1) as i32,
+            precise_x: -event.get_double_value_field(EventField::SCROLL_WHEEL_EVENT_FIXED_POINT_DELTA_AXIS_2),
+            precise_y: event.get_double_value_field(EventField::SCROLL_WHEEL_EVENT_FIXED_POINT_DELTA_AXIS_1),
+        },
+        _ => return None,
+    };
+    Some(kind)
+}
+
+fn button_number(event: &CGEvent) -> MouseButton {
+    // Quartz numbers buttons from 0 (left).
+    match event.get_integer_value_field(EventField::MOUSE_EVENT_BUTTON_NUMBER) {
+        0 => MouseButton::Left,
+        1 => MouseButton::Right,
+        2 => MouseButton::Middle,
+        n => MouseButton::Other((n + 1).clamp(0, u8::MAX as i64) as u8),
+    }
+}
+
+fn keycode(event: &CGEvent) -> Option<Keycode> {
+    let code = event.get_integer_value_field(EventField::KEYBOARD_EVENT_KEYCODE);
+    let key = match code {
+        0x00 => Keycode::A,
+        0x01 => Keycode::S,
+        0x02 => Keycode::D,
+        0x03 => Keycode::F,
+        0x04 => Keycode::H,
+        0x05 => Keycode::G,
+        0x06 => Keycode::Z,
+        0x07 => Keycode::X,
+        0x08 => Keycode::C,
+        0x09 => Keycode::V,
+        0x0B => Keycode::B,
+        0x0C => Keycode::Q,
+        0x0D => Keycode::W,
+        0x0E => Keycode::E,
+        0x0F => Keycode::R,
+        0x10 => Keycode::Y,
+        0x11 => Keycode::T,
+        0x12 => Keycode::Key1,
+        0x13 => Keycode::Key2,
+        0x14 => Keycode::Key3,
+        0x15 => Keycode::Key4,
+        0x16 => Keycode::Key6,
+        0x17 => Keycode::Key5,
+        0x18 => Keycode::Equal,
+        0x19 => Keycode::Key9,
+        0x1A => Keycode::Key7,
+        0x1B => Keycode::Minus,
+        0x1C => Keycode::Key8,
+        0x1D => Keycode::Key0,
+        0x1E => Keycode::RightBracket,
+        0x1F => Keycode::O,
+        0x20 => Keycode::U,
+        0x21 => Keycode::LeftBracket,
+        0x22 => Keycode::I,
+        0x23 => Keycode::P,
+        0x24 => Keycode::Enter,
+        0x25 => Keycode::L,
+        0x26 => Keycode::J,
+        0x27 => Keycode::Apostrophe,
+        0x28 => Keycode::K,
+        0x29 => Keycode::Semicolon,
+        0x2A => Keycode::BackSlash,
+        0x2B => Keycode::Comma,
+        0x2C => Keycode::Slash,
+        0x2D => Keycode::N,
+        0x2E => Keycode::M,
+        0x2F => Keycode::Dot,
+        0x30 => Keycode::Tab,
+        0x31 => Keycode::Space,
+        0x32 => Keycode::Grave,
+        0x33 => Keycode::Backspace,
+        0x35 => Keycode::Escape,
+        0x36 | 0x37 => Keycode::Meta,
+        0x38 => Keycode::LShift,
+        0x39 => Keycode::CapsLock,
+        0x3A => Keycode::LAlt,
+        0x3B => Keycode::LControl,
+        0x3C => Keycode::RShift,
+        0x3D => Keycode::RAlt,
+        0x3E => Keycode::RControl,
+        0x43 => Keycode::NumpadMultiply,
+        0x45 => Keycode::NumpadAdd,
+        0x4B => Keycode::NumpadDivide,
+        0x4C => Keycode::Enter,
+        0x4E => Keycode::NumpadSubtract,
+        0x52 => Keycode::Numpad0,
+        0x53 => Keycode::Numpad1,
+        0x54 => Keycode::Numpad2,
+        0x55 => Keycode::Numpad3,
+        0x56 => Keycode::Numpad4,
+        0x57 => Keycode::Numpad5,
+        0x58 => Keycode::Numpad6,
+        0x59 => Keycode::Numpad7,
+        0x5B => Keycode::Numpad8,
+        0x5C => Keycode::Numpad9,
+        0x60 => Keycode::F5,
+        0x61 => Keycode::F6,
+        0x62 => Keycode::F7,
+        0x63 => Keycode::F3,
+        0x64 => Keycode::F8,
+        0x65 => Keycode::F9,
+        0x67 => Keycode::F11,
+        0x6D => Keycode::F10,
+        0x6F => Keycode::F12,
+        0x72 => Keycode::Insert,
+        0x73 => Keycode::Home,
+        0x74 => Keycode::PageUp,
+        0x75 => Keycode::Delete,
+        0x76 => Keycode::F4,
+        0x77 => Keycode::End,
+        0x78 => Keycode::F2,
+        0x79 => Keycode::PageDown,
+        0x7A => Keycode::F1,
+        0x7B => Keycode::Left,
+        0x7C => Keycode::Right,
+        0x7D => Keycode::Down,
+        0x7E => Keycode::Up,
+        _ => return None,
+    };
+    Some(key)
+}
@@ -0,0 +1,202 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use device_query::Keycode;
+use tokio::sync::mpsc;
+
+use crate::config::InputConfig;
+
+pub mod compare;
+#[cfg(target_os = "macos")]
+pub mod event_tap;
+pub mod evdev;
+pub mod poller;
+#[cfg(test)]
+pub mod scripted;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Other(u8),
+}
+
+impl MouseButton {
+    /// Maps an index into device_query's 1-based `button_pressed` to a
+    /// button. Its order follows the platform: X11's Button1..Button5
+    /// masks on Linux, where 4 and 5 are the wheel and so aren't buttons
+    /// at all, and left, right, middle elsewhere.
+    pub fn from_index(index: usize) -> Option<Self> {
+        match index {
+            1 => Some(MouseButton::Left),
+            #[cfg(target_os = "linux")]
+            2 => Some(MouseButton::Middle),
+            #[cfg(target_os = "linux")]
+            3 => Some(MouseButton::Right),
+            #[cfg(target_os = "linux")]
+            4 | 5 => None,
+            #[cfg(not(target_os = "linux"))]
+            2 => Some(MouseButton::Right),
+            #[cfg(not(target_os = "linux"))]
+            3 => Some(MouseButton::Middle),
+            n => Some(MouseButton::Other(n.min(u8::MAX as usize) as u8)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEventKind {
+    KeyDown(Keycode),
+    KeyUp(Keycode),
+    ButtonDown(MouseButton),
+    ButtonUp(MouseButton),
+    /// Absolute pointer position in global screen coordinates.
+    PointerMoved { x: i32, y: i32 },
+    /// Relative pointer motion, for sources that never see the cursor.
+    PointerDelta { dx: i32, dy: i32 },
+    /// Wheel or touchpad scrolling. Positive `delta_y` scrolls up and
+    /// positive `delta_x` scrolls right. The `delta_*` fields are whole
+    /// wheel steps; `precise_*` are the high-resolution deltas in
+    /// fractions of a step.
+    Scroll {
+        delta_x: i32,
+        delta_y: i32,
+        precise_x: f64,
+        precise_y: f64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputEvent {
+    pub time: DateTime<Utc>,
+    pub kind: InputEventKind,
+}
+
+impl InputEvent {
+    pub fn now(kind: InputEventKind) -> Self {
+        Self { time: Utc::now(), kind }
+    }
+}
+
+/// A producer of raw input events.
+///
+/// `next_event` blocks until an event is available and returns `Ok(None)`
+/// once the source is exhausted.
+pub trait InputSource {
+    fn name(&self) -> &'static str;
+    fn next_event(&mut self) -> Result<Option<InputEvent>>;
+}
+
+/// Opens the input source selected in the config.
+///
+/// `auto` prefers the platform's event-driven source and only falls back to
+/// polling device_query when that can't be opened.
+pub fn open(config: &InputConfig) -> Result<Box<dyn InputSource>> {
+    match config.backend.as_str() {
+        "device_query" => Ok(Box::new(poller::DeviceQueryPoller::new())),
+        "evdev" => Ok(Box::new(evdev::EvdevSource::open(&config.evdev_devices)?)),
+        #[cfg(target_os = "macos")]
+        "event_tap" => Ok(Box::new(event_tap::EventTapSource::open()?)),
+        "auto" | "" => match open_event_driven(config) {
+            Ok(source) => Ok(source),
+            Err(e) => {
+                log::warn!("Event-driven capture unavailable ({}), falling back to polling", e);
+                Ok(Box::new(poller::DeviceQueryPoller::new()))
+            }
+        },
+        other => bail!("Unknown input backend: {}", other),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn open_event_driven(_config: &InputConfig) -> Result<Box<dyn InputSource>> {
+    Ok(Box::new(event_tap::EventTapSource::open()?))
+}
+
+#[cfg(target_os = "linux")]
+fn open_event_driven(config: &InputConfig) -> Result<Box<dyn InputSource>> {
+    Ok(Box::new(evdev::EvdevSource::open(&config.evdev_devices)?))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn open_event_driven(_config: &InputConfig) -> Result<Box<dyn InputSource>> {
+    bail!("No event-driven input source for this platform")
+}
+
+/// Runs an input source on a dedicated thread and forwards its events.
+///
+/// The source is opened on that thread, so it doesn't need to be `Send`.
+pub fn spawn<F>(open: F) -> mpsc::UnboundedReceiver<InputEvent>
+where
+    F: FnOnce() -> Result<Box<dyn InputSource>> + Send + 'static,
+{
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    std::thread::spawn(move || {
+        let mut source = match open() {
+            Ok(source) => source,
+            Err(e) => {
+                log::error!("Failed to open input source: {}", e);
+                return;
+            }
+        };
+        log::info!("Capturing input from {}", source.name());
+
+        loop {
+            match source.next_event() {
+                Ok(Some(event)) => {
+                    if tx.send(event).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => {
+                    log::info!("Input source {} finished", source.name());
+                    break;
+                }
+                Err(e) => {
+                    log::error!("Input source {} failed: {}", source.name(), e);
+                    break;
+                }
+            }
+        }
+    });
+
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn x11_button_masks_map_to_buttons() {
+        let buttons: Vec<_> = (1..=6).map(MouseButton::from_index).collect();
+        assert_eq!(
+            buttons,
+            [
+                Some(MouseButton::Left),
+                Some(MouseButton::Middle),
+                Some(MouseButton::Right),
+                None,
+                None,
+                Some(MouseButton::Other(6)),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn buttons_are_left_right_middle() {
+        let buttons: Vec<_> = (1..=4).map(MouseButton::from_index).collect();
+        assert_eq!(
+            buttons,
+            [
+                Some(MouseButton::Left),
+                Some(MouseButton::Right),
+                Some(MouseButton::Middle),
+                Some(MouseButton::Other(4)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use device_query::{DeviceQuery, DeviceState, Keycode, MouseState};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use super::{InputEvent, InputEventKind, InputSource, MouseButton};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Samples device_query state every `POLL_INTERVAL` and turns the
+/// differences between samples into events.
+///
+/// device_query can't see the scroll wheel, so this source never reports
+/// scrolling.
+pub struct DeviceQueryPoller {
+    device_state: DeviceState,
+    last_mouse: MouseState,
+    last_keys: Vec<Keycode>,
+    pending: VecDeque<InputEvent>,
+}
+
+impl DeviceQueryPoller {
+    pub fn new() -> Self {
+        let device_state = DeviceState::new();
+        let last_mouse = device_state.get_mouse();
+        let last_keys = device_state.get_keys();
+
+        let mut pending = VecDeque::new();
+        pending.push_back(InputEvent::now(InputEventKind::PointerMoved {
+            x: last_mouse.coords.0,
+            y: last_mouse.coords.1,
+        }));
+
+        Self {
+            device_state,
+            last_mouse,
+            last_keys,
+            pending,
+        }
+    }
+
+    fn poll(&mut self) {
+        let current_mouse = self.device_state.get_mouse();
+        let current_keys = self.device_state.get_keys();
+
+        for key in current_keys.iter().filter(|k| !self.last_keys.contains(k)) {
+            self.pending.push_back(InputEvent::now(InputEventKind::KeyDown(*key)));
+        }
+        for key in self.last_keys.iter().filter(|k| !current_keys.contains(k)) {
+            self.pending.push_back(InputEvent::now(InputEventKind::KeyUp(*key)));
+        }
+
+        let buttons = self.last_mouse.button_pressed.iter()
+            .zip(current_mouse.button_pressed.iter())
+            .enumerate();
+        for (index, (prev, curr)) in buttons {
+            let Some(button) = MouseButton::from_index(index) else {
+                continue;
+            };
+            if !prev && *curr {
+                self.pending.push_back(InputEvent::now(InputEventKind::ButtonDown(button)));
+            } else if *prev && !curr {
+                self.pending.push_back(InputEvent::now(InputEventKind::ButtonUp(button)));
+            }
+        }
+
+        if current_mouse.coords != self.last_mouse.coords {
+            self.pending.push_back(InputEvent::now(InputEventKind::PointerMoved {
+                x: current_mouse.coords.0,
+                y: current_mouse.coords.1,
+            }));
+        }
+
+        self.last_mouse = current_mouse;
+        self.last_keys = current_keys;
+    }
+}
+
+impl InputSource for DeviceQueryPoller {
+    fn name(&self) -> &'static str {
+        "device_query poller"
+    }
+
+    fn next_event(&mut self) -> Result<Option<InputEvent>> {
+        while self.pending.is_empty() {
+            std::thread::sleep(POLL_INTERVAL);
+            self.poll();
+        }
+        Ok(self.pending.pop_front())
+    }
+}
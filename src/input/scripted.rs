@@ -0,0 +1,28 @@
+use anyhow::Result;
+use std::collections::VecDeque;
+
+use super::{InputEvent, InputSource};
+
+/// Replays a fixed list of events, e.g. to drive the metrics accumulator
+/// from a script instead of real hardware.
+pub struct ScriptedSource {
+    events: VecDeque<InputEvent>,
+}
+
+impl ScriptedSource {
+    pub fn new(events: impl IntoIterator<Item = InputEvent>) -> Self {
+        Self {
+            events: events.into_iter().collect(),
+        }
+    }
+}
+
+impl InputSource for ScriptedSource {
+    fn name(&self) -> &'static str {
+        "scripted"
+    }
+
+    fn next_event(&mut self) -> Result<Option<InputEvent>> {
+        Ok(self.events.pop_front())
+    }
+}
@@ -0,0 +1,176 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+
+use crate::metrics::Interval;
+
+/// Counts that haven't reached the database yet, kept on disk so they
+/// survive a crash or a failed save. The saver rewrites it as it goes and
+/// whatever is in it at startup is saved with the first interval, unless
+/// an interval with its id was saved before the crash.
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    pub fn open() -> Result<Self> {
+        let proj_dirs = ProjectDirs::from("com", "kweeb-logger", "logger")
+            .context("Failed to get project directories")?;
+
+        let data_dir = proj_dirs.data_dir();
+        std::fs::create_dir_all(data_dir)?;
+
+        Ok(Self::at(data_dir.join("pending-metrics.json")))
+    }
+
+    pub fn at(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The unsaved interval left by the last run, if any. A journal that
+    /// can't be parsed is moved aside, so it doesn't stop the app from
+    /// starting, and is left for the user to look at.
+    pub fn load(&self) -> Result<Option<Interval>> {
+        let contents = match std::fs::read(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context("Failed to read metrics journal"),
+        };
+        match serde_json::from_slice(&contents) {
+            Ok(interval) => Ok(Some(interval)),
+            Err(e) => {
+                let corrupt_path = self.path.with_extension("json.corrupt");
+                log::warn!(
+                    "Failed to parse metrics journal {}, moving it to {}: {}",
+                    self.path.display(),
+                    corrupt_path.display(),
+                    e
+                );
+                std::fs::rename(&self.path, &corrupt_path)
+                    .context("Failed to move corrupt metrics journal aside")?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Replaces the journal with `interval`. The old contents stay in place
+    /// until the new ones are fully on disk.
+    pub fn write(&self, interval: &Interval) -> Result<()> {
+        if interval.metrics.is_empty() {
+            return self.clear();
+        }
+
+        let tmp_path = self.path.with_extension("json.tmp");
+        let contents = serde_json::to_vec(interval)?;
+        let mut file = std::fs::File::create(&tmp_path)
+            .context("Failed to create metrics journal")?;
+        std::io::Write::write_all(&mut file, &contents)
+            .and_then(|_| file.sync_all())
+            .context("Failed to write metrics journal")?;
+        std::fs::rename(&tmp_path, &self.path)
+            .context("Failed to replace metrics journal")?;
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).context("Failed to remove metrics journal")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{Metrics, PendingMetrics};
+    use device_query::Keycode;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    // A journal in a directory of its own, removed once the test is done.
+    struct TempJournal {
+        dir: PathBuf,
+        journal: Journal,
+    }
+
+    impl TempJournal {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("kweeb-journal-test-{}", Uuid::new_v4()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let journal = Journal::at(dir.join("pending-metrics.json"));
+            Self { dir, journal }
+        }
+    }
+
+    impl Drop for TempJournal {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn pending() -> PendingMetrics {
+        let pending = PendingMetrics::default();
+        pending.add(&Metrics {
+            keypresses: 3,
+            mouse_distance_in: 1.5,
+            key_counts: HashMap::from([(Keycode::A, 2), (Keycode::Space, 1)]),
+            ..Default::default()
+        });
+        pending
+    }
+
+    #[test]
+    fn replays_what_was_written() {
+        let temp = TempJournal::new();
+        assert!(temp.journal.load().unwrap().is_none());
+
+        let written = pending().snapshot();
+        temp.journal.write(&written).unwrap();
+        let loaded = temp.journal.load().unwrap().unwrap();
+        assert_eq!(loaded.id, written.id);
+        assert_eq!(loaded.start, written.start);
+        assert!(loaded.metrics == written.metrics);
+
+        // Restored into a fresh run, it's saved under the same id.
+        let restored = PendingMetrics::default();
+        restored.restore(&loaded);
+        let interval = restored.take();
+        assert_eq!(interval.id, written.id);
+        assert_eq!(interval.metrics.keypresses, 3);
+        assert_eq!(interval.metrics.key_counts[&Keycode::A], 2);
+    }
+
+    #[test]
+    fn nothing_pending_clears_the_journal() {
+        let temp = TempJournal::new();
+        temp.journal.write(&pending().snapshot()).unwrap();
+        temp.journal.write(&PendingMetrics::default().snapshot()).unwrap();
+        assert!(temp.journal.load().unwrap().is_none());
+        assert!(!temp.journal.path.exists());
+    }
+
+    #[test]
+    fn torn_or_corrupt_journal_is_moved_aside() {
+        let temp = TempJournal::new();
+        temp.journal.write(&pending().snapshot()).unwrap();
+        let contents = std::fs::read(&temp.journal.path).unwrap();
+        let corrupt_path = temp.journal.path.with_extension("json.corrupt");
+
+        for bad in [&contents[..contents.len() / 2], b"\0\0\0\0".as_slice(), b"".as_slice()] {
+            std::fs::write(&temp.journal.path, bad).unwrap();
+            assert!(temp.journal.load().unwrap().is_none());
+            assert!(!temp.journal.path.exists());
+            assert_eq!(std::fs::read(&corrupt_path).unwrap(), bad);
+        }
+
+        // A write that never finished leaves the journal before it intact.
+        let written = pending().snapshot();
+        temp.journal.write(&written).unwrap();
+        std::fs::write(temp.journal.path.with_extension("json.tmp"), &contents[..10]).unwrap();
+        assert_eq!(temp.journal.load().unwrap().unwrap().id, written.id);
+    }
+}
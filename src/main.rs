@@ -1,63 +1,120 @@
 use std::sync::Arc;
 use std::env;
 use tokio::runtime::Runtime;
+use tokio::signal::unix::{signal, SignalKind};
 use anyhow::Result;
+use clap::Parser;
 use dotenv::dotenv;
 
+mod activity;
 mod app;
+mod breaks;
+mod cli;
+mod clicks;
 mod config;
 mod db;
+mod device;
+mod heatmap;
+mod input;
+mod journal;
 mod logger;
 mod metrics;
 mod monitor;
-mod scroll;
+mod notify;
+mod sink;
 mod supabase;
+mod sync;
 mod menubar;
 mod tasks;
+mod typing;
 
 use crate::app::AppState;
+use crate::cli::Cli;
 use crate::config::Config;
+use crate::tasks::activity::save_activity_sessions;
+use crate::tasks::breaks::remind_breaks;
 use crate::tasks::metrics::{collect_metrics, save_metrics_with_updates};
 use crate::tasks::monitor::refresh_monitors_periodically;
-use crate::supabase::SupabaseClient;
+use crate::tasks::retention::enforce_retention;
+use crate::tasks::sync::sync_outbox;
 
 
 fn main() -> Result<()> {
     dotenv().ok();
     env_logger::init();
-    log::info!("Starting keyboard logger...");
+    let cli = Cli::parse();
 
     let config = Config::load()?;
+
     let rt = Runtime::new()?;
+    if let Some(command) = cli.command {
+        return rt.block_on(cli::run(command, &config));
+    }
+
+    log::info!("Starting keyboard logger...");
 
     log::info!("SUPABASE_URL: {}", env::var("SUPABASE_URL").unwrap_or_else(|_| "not set".to_string()));
     log::info!("SUPABASE_ANON_KEY: {}", env::var("SUPABASE_ANON_KEY").map(|k| "is set".to_string()).unwrap_or_else(|_| "not set".to_string()));
 
-    let state = rt.block_on(AppState::initialize())?;
+    let state = rt.block_on(AppState::initialize(&config))?;
 
-    let supabase = if config.has_supabase_config() {
-        Some(Arc::new(SupabaseClient::new(
-            config.supabase.url.as_ref().unwrap(),
-            config.supabase.api_key.as_ref().unwrap(),
-        )?))
-    } else {
+    if !config.has_supabase_config() {
         log::warn!("Supabase configuration not found, skipping...");
-        None
-    };
+    }
+    let sinks = sink::open(&config, &state.device_id)?;
 
 
-    rt.spawn(collect_metrics(Arc::clone(&state)));
-    rt.spawn(save_metrics_with_updates(
+    rt.spawn(collect_metrics(Arc::clone(&state), config.input.clone()));
+    // What's saved while Supabase is off isn't queued for it, so the
+    // backfill has to know when that was.
+    if config.has_supabase_config() {
+        rt.block_on(state.db.start_sync(chrono::Utc::now()))?;
+    } else {
+        rt.block_on(state.db.pause_sync(chrono::Utc::now()))?;
+    }
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let saver = rt.spawn(save_metrics_with_updates(
         Arc::clone(&state),
-        supabase.clone(),
+        sinks.iter().map(|sink| sink.name().to_string()).collect(),
+        shutdown_rx.clone(),
     ));
+    for sink in sinks {
+        rt.spawn(sync_outbox(Arc::clone(&state), sink, config.sync.clone()));
+    }
     rt.spawn(refresh_monitors_periodically(Arc::clone(&state)));
+    let session_saver = rt.spawn(save_activity_sessions(Arc::clone(&state), shutdown_rx));
+    rt.spawn(enforce_retention(Arc::clone(&state), config.retention.clone()));
+    if config.breaks.enabled {
+        rt.spawn(remind_breaks(Arc::clone(&state), config.breaks.clone()));
+    }
 
     rt.block_on(async {
-        loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        wait_for_shutdown().await?;
+        log::info!("Shutting down, saving pending metrics...");
+        shutdown_tx.send_replace(true);
+        let (metrics, sessions) = tokio::join!(
+            tokio::time::timeout(SHUTDOWN_TIMEOUT, saver),
+            tokio::time::timeout(SHUTDOWN_TIMEOUT, session_saver),
+        );
+        if metrics.is_err() {
+            log::warn!("Timed out saving pending metrics, they stay in the journal");
+        }
+        if sessions.is_err() {
+            log::warn!("Timed out saving the current activity session");
         }
-    });
+        Ok(())
+    })
+}
+
+// How long the final save may take before we exit anyway. Anything it
+// didn't get to is in the journal as of the last checkpoint.
+const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
 
+async fn wait_for_shutdown() -> Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = terminate.recv() => {}
+    }
     Ok(())
 }
@@ -1,10 +1,12 @@
-use std::io::Write;
+use std::io::{BufRead, BufReader, Write};
 use std::os::unix::net::UnixStream;
 use std::process::Command;
 use std::time::Duration;
 use std::thread;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use anyhow::{Result, Context};
+use tokio::sync::mpsc;
+use crate::metrics::{TotalMetrics, TypingSpeed};
 
 const MAX_RETRIES: u32 = 20;
 const RETRY_DELAY: Duration = Duration::from_millis(250);
@@ -12,34 +14,82 @@ const SOCKET_PATH: &str = "/tmp/kawaiilogger.sock";
 
 #[derive(Debug, Serialize)]
 pub struct MenuMetrics {
-    pub keypresses: i32,
-    pub mouse_clicks: i32,
+    pub keypresses: i64,
+    pub mouse_clicks: i64,
     pub mouse_distance_in: f64,
     pub mouse_distance_mi: f64,
-    pub scroll_steps: i32,
+    pub scroll_steps: i64,
+    pub left_clicks: i64,
+    pub right_clicks: i64,
+    pub middle_clicks: i64,
+    pub extra_clicks: i64,
+    pub double_clicks: i64,
+    pub triple_clicks: i64,
+    pub drags: i64,
+    pub drag_distance_in: f64,
+    /// Minutes spent in activity sessions today.
+    pub active_minutes: f64,
+    /// Today's typing rates, in words per minute.
+    pub typing_avg_wpm: f64,
+    pub typing_peak_wpm_1m: f64,
+    pub typing_peak_wpm_5m: f64,
 }
 
-impl MenuMetrics {
-    pub fn new(
-        keypresses: i32,
-        mouse_clicks: i32,
-        mouse_distance_in: f64,
-        mouse_distance_mi: f64,
-        scroll_steps: i32,
-    ) -> Self {
+impl From<&TotalMetrics> for MenuMetrics {
+    fn from(total: &TotalMetrics) -> Self {
         Self {
-            keypresses,
-            mouse_clicks,
-            mouse_distance_in,
-            mouse_distance_mi,
-            scroll_steps,
+            keypresses: total.total_keypresses,
+            mouse_clicks: total.total_mouse_clicks,
+            mouse_distance_in: total.total_mouse_distance_in,
+            mouse_distance_mi: total.total_mouse_distance_mi,
+            scroll_steps: total.total_scroll_steps,
+            left_clicks: total.total_left_clicks,
+            right_clicks: total.total_right_clicks,
+            middle_clicks: total.total_middle_clicks,
+            extra_clicks: total.total_extra_clicks,
+            double_clicks: total.total_double_clicks,
+            triple_clicks: total.total_triple_clicks,
+            drags: total.total_drags,
+            drag_distance_in: total.total_drag_distance_in,
+            active_minutes: 0.0,
+            typing_avg_wpm: 0.0,
+            typing_peak_wpm_1m: 0.0,
+            typing_peak_wpm_5m: 0.0,
         }
     }
 }
 
+impl MenuMetrics {
+    pub fn with_today(mut self, typing: &TypingSpeed, active_minutes: f64) -> Self {
+        self.active_minutes = active_minutes;
+        self.typing_avg_wpm = typing.avg_wpm();
+        self.typing_peak_wpm_1m = typing.peak_wpm_1m();
+        self.typing_peak_wpm_5m = typing.peak_wpm_5m();
+        self
+    }
+}
+
+// Messages to the menubar, one JSON object per line.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum MenuMessage<'a> {
+    Metrics(&'a MenuMetrics),
+    BreakReminder { id: i64, active_minutes: f64 },
+    BreakCleared,
+}
+
+/// Something the user did in the menubar.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MenuAction {
+    SnoozeBreak { id: i64 },
+    SkipBreak { id: i64 },
+}
+
 pub struct MenuBar {
     socket: UnixStream,
     go_process: std::process::Child,
+    actions: Option<mpsc::UnboundedReceiver<MenuAction>>,
 }
 
 impl MenuBar {
@@ -63,13 +113,46 @@ impl MenuBar {
 
         // Try to connect with retries
         let socket = Self::connect_with_retry()?;
+        let actions = Self::spawn_action_reader(&socket)?;
 
         Ok(MenuBar {
             socket,
             go_process,
+            actions: Some(actions),
         })
     }
 
+    fn spawn_action_reader(socket: &UnixStream) -> Result<mpsc::UnboundedReceiver<MenuAction>> {
+        let reader = socket.try_clone().context("Failed to clone menubar socket")?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(e) => {
+                        log::warn!("Stopped reading from menubar socket: {}", e);
+                        return;
+                    }
+                };
+                match serde_json::from_str::<MenuAction>(&line) {
+                    Ok(action) => {
+                        if tx.send(action).is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => log::warn!("Ignoring menubar message {:?}: {}", line, e),
+                }
+            }
+        });
+        Ok(rx)
+    }
+
+    /// Actions the user takes in the menubar. Only the first caller gets
+    /// them.
+    pub fn take_actions(&mut self) -> Option<mpsc::UnboundedReceiver<MenuAction>> {
+        self.actions.take()
+    }
+
 
     fn connect_with_retry() -> Result<UnixStream> {
         for i in 0..MAX_RETRIES {
@@ -93,8 +176,21 @@ impl MenuBar {
     }
 
     pub fn update_metrics(&mut self, metrics: &MenuMetrics) -> Result<()> {
-        let json = serde_json::to_string(metrics)?;
-        println!("Sending metrics update: {}", json);
+        self.send(&MenuMessage::Metrics(metrics))
+    }
+
+    pub fn show_break_reminder(&mut self, id: i64, active_minutes: f64) -> Result<()> {
+        self.send(&MenuMessage::BreakReminder { id, active_minutes })
+    }
+
+    pub fn clear_break_reminder(&mut self) -> Result<()> {
+        self.send(&MenuMessage::BreakCleared)
+    }
+
+    fn send(&mut self, message: &MenuMessage) -> Result<()> {
+        let mut json = serde_json::to_string(message)?;
+        println!("Sending menubar message: {}", json);
+        json.push('\n');
         self.socket.write_all(json.as_bytes())?;
         Ok(())
     }
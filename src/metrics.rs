@@ -1,27 +1,328 @@
-#[derive(Default, Clone)]
+use chrono::{DateTime, Utc};
+use device_query::Keycode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::typing::KEYS_PER_WORD;
+
+#[derive(Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Metrics {
-    pub keypresses: i32,
-    pub mouse_clicks: i32,
+    pub keypresses: i64,
+    pub mouse_clicks: i64,
     pub mouse_distance_in: f64,
     pub mouse_distance_mi: f64,
-    pub scroll_steps: i32,
+    pub scroll_steps: i64,
+    pub scroll_up: i64,
+    pub scroll_down: i64,
+    pub scroll_left: i64,
+    pub scroll_right: i64,
+    pub scroll_vertical_precise: f64,
+    pub scroll_horizontal_precise: f64,
+    pub left_clicks: i64,
+    pub right_clicks: i64,
+    pub middle_clicks: i64,
+    pub extra_clicks: i64,
+    pub double_clicks: i64,
+    pub triple_clicks: i64,
+    pub drags: i64,
+    pub drag_distance_in: f64,
+    /// Key presses inside typing bursts, not counting the first key of each
+    /// burst, and the time those bursts took.
+    pub typing_keys: i64,
+    pub typing_seconds: f64,
+    /// Highest keys per minute over any trailing 1 or 5 minute window that
+    /// ended in this interval.
+    pub peak_kpm_1m: f64,
+    pub peak_kpm_5m: f64,
+    /// Presses per key. Only counts are kept, never the order keys were
+    /// pressed in.
+    #[serde(with = "key_names")]
+    pub key_counts: HashMap<Keycode, i64>,
 }
 
 impl Metrics {
-    pub fn reset(&mut self) {
-        self.keypresses = 0;
-        self.mouse_clicks = 0;
-        self.mouse_distance_in = 0.0;
-        self.mouse_distance_mi = 0.0;
-        self.scroll_steps = 0;
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn add(&mut self, other: &Metrics) {
+        self.keypresses += other.keypresses;
+        self.mouse_clicks += other.mouse_clicks;
+        self.mouse_distance_in += other.mouse_distance_in;
+        self.mouse_distance_mi += other.mouse_distance_mi;
+        self.scroll_steps += other.scroll_steps;
+        self.scroll_up += other.scroll_up;
+        self.scroll_down += other.scroll_down;
+        self.scroll_left += other.scroll_left;
+        self.scroll_right += other.scroll_right;
+        self.scroll_vertical_precise += other.scroll_vertical_precise;
+        self.scroll_horizontal_precise += other.scroll_horizontal_precise;
+        self.left_clicks += other.left_clicks;
+        self.right_clicks += other.right_clicks;
+        self.middle_clicks += other.middle_clicks;
+        self.extra_clicks += other.extra_clicks;
+        self.double_clicks += other.double_clicks;
+        self.triple_clicks += other.triple_clicks;
+        self.drags += other.drags;
+        self.drag_distance_in += other.drag_distance_in;
+        self.typing_keys += other.typing_keys;
+        self.typing_seconds += other.typing_seconds;
+        self.peak_kpm_1m = self.peak_kpm_1m.max(other.peak_kpm_1m);
+        self.peak_kpm_5m = self.peak_kpm_5m.max(other.peak_kpm_5m);
+        for (key, count) in &other.key_counts {
+            *self.key_counts.entry(*key).or_insert(0) += count;
+        }
+    }
+
+    /// Keys per minute while typing.
+    pub fn typing_kpm(&self) -> f64 {
+        kpm(self.typing_keys as f64, self.typing_seconds)
+    }
+
+    pub fn typing_wpm(&self) -> f64 {
+        self.typing_kpm() / KEYS_PER_WORD
+    }
+
+    pub fn typing_speed(&self) -> TypingSpeed {
+        TypingSpeed::from_totals(self.typing_keys as f64, self.typing_seconds, self.peak_kpm_1m, self.peak_kpm_5m)
+    }
+}
+
+// Keycodes are written by name, the same as in the database.
+mod key_names {
+    use device_query::Keycode;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<S: Serializer>(counts: &HashMap<Keycode, i64>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(counts.iter().map(|(key, count)| (key.to_string(), count)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<Keycode, i64>, D::Error> {
+        HashMap::<String, i64>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(key, count)| Ok((key.parse().map_err(de::Error::custom)?, count)))
+            .collect()
+    }
+}
+
+/// Metrics counted over a span of time, `start` inclusive and `end`
+/// exclusive. The id is generated once and travels with the interval, so
+/// wherever it's sent, a second copy of it can be recognised.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Interval {
+    pub id: Uuid,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    #[serde(flatten)]
+    pub metrics: Metrics,
+}
+
+impl Interval {
+    fn starting(start: DateTime<Utc>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            start,
+            end: start,
+            metrics: Metrics::default(),
+        }
+    }
+}
+
+/// Metrics counted since the last save. The collector adds to it and the
+/// saver takes the whole interval at once, so every event is saved in
+/// exactly one interval. The lock is never held across an await.
+pub struct PendingMetrics {
+    interval: std::sync::Mutex<Interval>,
+}
+
+impl Default for PendingMetrics {
+    fn default() -> Self {
+        Self { interval: std::sync::Mutex::new(Interval::starting(Utc::now())) }
+    }
+}
+
+impl PendingMetrics {
+    pub fn add(&self, delta: &Metrics) {
+        self.lock().metrics.add(delta);
+    }
+
+    /// Everything counted so far, ending the interval and starting the next.
+    pub fn take(&self) -> Interval {
+        let now = Utc::now();
+        let mut interval = std::mem::replace(&mut *self.lock(), Interval::starting(now));
+        interval.end = now;
+        interval
+    }
+
+    /// Puts back an interval that couldn't be saved. It keeps its id and
+    /// start, and takes in whatever was counted since.
+    pub fn restore(&self, interval: &Interval) {
+        let mut pending = self.lock();
+        pending.id = interval.id;
+        pending.start = pending.start.min(interval.start);
+        pending.metrics.add(&interval.metrics);
+    }
+
+    /// The interval so far, as `take` would return it.
+    pub fn snapshot(&self) -> Interval {
+        let mut interval = self.lock().clone();
+        interval.end = Utc::now();
+        interval
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Interval> {
+        // A panic mid-update leaves at worst a partly counted event.
+        self.interval.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Average and peak typing rates over a period.
+#[derive(Debug, Default, Clone)]
+pub struct TypingSpeed {
+    pub avg_kpm: f64,
+    pub peak_kpm_1m: f64,
+    pub peak_kpm_5m: f64,
+}
+
+impl TypingSpeed {
+    pub fn from_totals(typing_keys: f64, typing_seconds: f64, peak_kpm_1m: f64, peak_kpm_5m: f64) -> Self {
+        Self {
+            avg_kpm: kpm(typing_keys, typing_seconds),
+            peak_kpm_1m,
+            peak_kpm_5m,
+        }
+    }
+
+    pub fn avg_wpm(&self) -> f64 {
+        self.avg_kpm / KEYS_PER_WORD
+    }
+
+    pub fn peak_wpm_1m(&self) -> f64 {
+        self.peak_kpm_1m / KEYS_PER_WORD
+    }
+
+    pub fn peak_wpm_5m(&self) -> f64 {
+        self.peak_kpm_5m / KEYS_PER_WORD
+    }
+}
+
+fn kpm(keys: f64, seconds: f64) -> f64 {
+    if seconds > 0.0 {
+        keys * 60.0 / seconds
+    } else {
+        0.0
     }
 }
 
 #[derive(Default, Clone)]
 pub struct TotalMetrics {
-    pub total_keypresses: i32,
-    pub total_mouse_clicks: i32,
+    pub total_keypresses: i64,
+    pub total_mouse_clicks: i64,
     pub total_mouse_distance_in: f64,
     pub total_mouse_distance_mi: f64,
-    pub total_scroll_steps: i32,
-}
\ No newline at end of file
+    pub total_scroll_steps: i64,
+    pub total_left_clicks: i64,
+    pub total_right_clicks: i64,
+    pub total_middle_clicks: i64,
+    pub total_extra_clicks: i64,
+    pub total_double_clicks: i64,
+    pub total_triple_clicks: i64,
+    pub total_drags: i64,
+    pub total_drag_distance_in: f64,
+}
+
+impl TotalMetrics {
+    pub fn add(&mut self, metrics: &Metrics) {
+        self.total_keypresses += metrics.keypresses;
+        self.total_mouse_clicks += metrics.mouse_clicks;
+        self.total_mouse_distance_in += metrics.mouse_distance_in;
+        self.total_mouse_distance_mi += metrics.mouse_distance_mi;
+        self.total_scroll_steps += metrics.scroll_steps;
+        self.total_left_clicks += metrics.left_clicks;
+        self.total_right_clicks += metrics.right_clicks;
+        self.total_middle_clicks += metrics.middle_clicks;
+        self.total_extra_clicks += metrics.extra_clicks;
+        self.total_double_clicks += metrics.double_clicks;
+        self.total_triple_clicks += metrics.triple_clicks;
+        self.total_drags += metrics.drags;
+        self.total_drag_distance_in += metrics.drag_distance_in;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn concurrent_adds_are_saved_exactly_once() {
+        const COLLECTORS: usize = 8;
+        const EVENTS: i64 = 20_000;
+
+        let pending = Arc::new(PendingMetrics::default());
+        let done = Arc::new(AtomicBool::new(false));
+
+        // Every third save fails and the interval is put back.
+        let saver = {
+            let pending = pending.clone();
+            let done = done.clone();
+            std::thread::spawn(move || {
+                let mut saved = Vec::new();
+                let mut attempt = 0;
+                loop {
+                    let finished = done.load(Ordering::SeqCst);
+                    let interval = pending.take();
+                    attempt += 1;
+                    if !finished && attempt % 3 == 0 {
+                        pending.restore(&interval);
+                    } else if !interval.metrics.is_empty() {
+                        saved.push(interval);
+                    }
+                    if finished {
+                        return saved;
+                    }
+                    std::thread::yield_now();
+                }
+            })
+        };
+
+        let collectors: Vec<_> = (0..COLLECTORS)
+            .map(|_| {
+                let pending = pending.clone();
+                std::thread::spawn(move || {
+                    let event = Metrics {
+                        keypresses: 1,
+                        mouse_distance_in: 0.5,
+                        key_counts: HashMap::from([(Keycode::A, 1)]),
+                        ..Default::default()
+                    };
+                    for _ in 0..EVENTS {
+                        pending.add(&event);
+                    }
+                })
+            })
+            .collect();
+        for collector in collectors {
+            collector.join().unwrap();
+        }
+        done.store(true, Ordering::SeqCst);
+        let saved = saver.join().unwrap();
+
+        let mut total = Metrics::default();
+        for interval in &saved {
+            total.add(&interval.metrics);
+        }
+        let expected = COLLECTORS as i64 * EVENTS;
+        assert_eq!(total.keypresses, expected);
+        assert_eq!(total.mouse_distance_in, expected as f64 * 0.5);
+        assert_eq!(total.key_counts[&Keycode::A], expected);
+        assert!(pending.take().metrics.is_empty());
+
+        let ids: HashSet<Uuid> = saved.iter().map(|interval| interval.id).collect();
+        assert_eq!(ids.len(), saved.len(), "an interval was saved twice");
+    }
+}
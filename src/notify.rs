@@ -0,0 +1,26 @@
+use anyhow::{Context, Result};
+use std::process::Command;
+
+/// Shows a desktop notification using the platform's command line tool.
+pub fn desktop_notification(title: &str, body: &str) -> Result<()> {
+    let status = if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification \"{}\" with title \"{}\"",
+            applescript_escape(body),
+            applescript_escape(title),
+        );
+        Command::new("osascript").arg("-e").arg(script).status()
+    } else {
+        Command::new("notify-send").arg(title).arg(body).status()
+    }
+    .context("Failed to run notification command")?;
+
+    if !status.success() {
+        anyhow::bail!("Notification command exited with {}", status);
+    }
+    Ok(())
+}
+
+fn applescript_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use uuid::Uuid;
+
+use crate::config::{Config, HttpSinkConfig, SinkConfig};
+use crate::metrics::Interval;
+use crate::supabase::{SupabaseClient, SUPABASE_SINK};
+
+/// Somewhere saved intervals are sent. The save loop queues every interval
+/// in the sync outbox once for each sink, and each sink gets its own upload
+/// task, so one that's down doesn't hold up the others.
+#[async_trait]
+pub trait RemoteSink: Send + Sync {
+    /// The name the sink's intervals are queued under.
+    fn name(&self) -> &str;
+
+    /// Sends intervals in one request and returns the ids of those the sink
+    /// acknowledged. The rest are sent again later, as can be the
+    /// acknowledged ones if the acknowledgement is lost, so a sink has to
+    /// ignore ids it has already seen.
+    async fn send(&self, intervals: &[Interval]) -> Result<Vec<Uuid>>;
+}
+
+/// Sends batches of intervals to an HTTP endpoint as
+/// `{"device_id": ..., "intervals": [...]}`, each interval serialized as in
+/// the metrics journal. Any 2xx response acknowledges the whole batch.
+pub struct HttpSink {
+    name: String,
+    url: String,
+    device_id: String,
+    client: reqwest::Client,
+}
+
+impl HttpSink {
+    pub fn new(config: &HttpSinkConfig, device_id: String) -> Result<Self> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes())
+                    .with_context(|| format!("Invalid header name {:?} for sink {}", name, config.name))?,
+                HeaderValue::from_str(value)
+                    .with_context(|| format!("Invalid value for header {} of sink {}", name, config.name))?,
+            );
+        }
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(std::time::Duration::from_secs(30))
+            .build()?;
+
+        Ok(Self {
+            name: config.name.clone(),
+            url: config.url.clone(),
+            device_id,
+            client,
+        })
+    }
+}
+
+#[async_trait]
+impl RemoteSink for HttpSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn send(&self, intervals: &[Interval]) -> Result<Vec<Uuid>> {
+        let response = self.client
+            .post(&self.url)
+            .json(&serde_json::json!({
+                "device_id": self.device_id,
+                "intervals": intervals,
+            }))
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await?;
+            anyhow::bail!("{} request failed with {}: {}", self.name, status, error_text);
+        }
+
+        Ok(intervals.iter().map(|interval| interval.id).collect())
+    }
+}
+
+/// The sinks the config asks for: Supabase, if it's set up, and then the
+/// extra sinks in order, all sending as `device_id`.
+pub fn open(config: &Config, device_id: &str) -> Result<Vec<Arc<dyn RemoteSink>>> {
+    let mut sinks: Vec<Arc<dyn RemoteSink>> = Vec::new();
+    if config.has_supabase_config() {
+        sinks.push(Arc::new(SupabaseClient::new(
+            config.supabase.url.as_ref().unwrap(),
+            config.supabase.api_key.as_ref().unwrap(),
+            device_id.to_string(),
+        )?));
+    }
+
+    for sink in &config.sync.sinks {
+        let sink: Arc<dyn RemoteSink> = match sink {
+            SinkConfig::Http(config) => Arc::new(HttpSink::new(config, device_id.to_string())?),
+        };
+        if sink.name() == SUPABASE_SINK {
+            anyhow::bail!("The sink name {} is reserved for Supabase", SUPABASE_SINK);
+        }
+        if sinks.iter().any(|other| other.name() == sink.name()) {
+            anyhow::bail!("More than one sink is named {}", sink.name());
+        }
+        sinks.push(sink);
+    }
+    Ok(sinks)
+}
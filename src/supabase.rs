@@ -1,41 +1,28 @@
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, ACCEPT};
 use anyhow::{Context, Result};
-use std::env;
-use std::sync::Arc;
+use async_trait::async_trait;
+use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Metrics {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub id: Option<i64>,  
-    #[serde(skip_serializing_if = "Option::is_none")]  
-    pub created_at: Option<String>,
-    pub keypresses: i32,
-    pub mouse_clicks: i32,
-    pub mouse_distance_in: f64,
-    pub mouse_distance_mi: f64,
-    pub scroll_steps: i32,
-    pub device_id: String,
+use crate::metrics::Interval;
+use crate::sink::RemoteSink;
+
+/// The name Supabase's intervals are queued under in the sync outbox.
+pub const SUPABASE_SINK: &str = "supabase";
+
+#[derive(Debug, Deserialize)]
+struct Acknowledged {
+    interval_id: Uuid,
 }
 
 pub struct SupabaseClient {
     client: reqwest::Client,
     base_url: String,
-    api_key: String,
+    device_id: String,
 }
 
 impl SupabaseClient {
-    pub fn initialize_supabase() -> Result<Option<Arc<SupabaseClient>>> {
-        let supabase_url = env::var("SUPABASE_URL")
-            .context("SUPABASE_URL not set")?;
-        let supabase_key = env::var("SUPABASE_ANON_KEY")
-            .context("SUPABASE_ANON_KEY not set")?;
-    
-        let supabase = SupabaseClient::new(&supabase_url, &supabase_key)?;
-        Ok(Some(Arc::new(supabase)))
-    }
-
-    pub fn new(supabase_url: &str, api_key: &str) -> Result<Self> {
+    pub fn new(supabase_url: &str, api_key: &str, device_id: String) -> Result<Self> {
         let mut headers = HeaderMap::new();
         headers.insert(
             "apikey",
@@ -47,30 +34,55 @@ impl SupabaseClient {
         );
         headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
 
+        // Without a timeout a hung endpoint would stall syncing for good.
         let client = reqwest::Client::builder()
             .default_headers(headers)
+            .timeout(std::time::Duration::from_secs(30))
             .build()?;
 
         Ok(SupabaseClient {
             client,
             base_url: supabase_url.to_string(),
-            api_key: api_key.to_string(),
+            device_id,
         })
     }
 
-    pub async fn upsert_metrics(&self, metrics: &Metrics) -> Result<()> {
-        let url = format!("{}/rest/v1/rpc/upsert_metrics", self.base_url);
+    /// Adds intervals to the device's totals in one request and returns
+    /// the ids of those the server acknowledged. Sending an interval again
+    /// is harmless: the server ignores ids it has already seen.
+    pub async fn upsert_metrics_batch(&self, intervals: &[Interval]) -> Result<Vec<Uuid>> {
+        let url = format!("{}/rest/v1/rpc/upsert_metrics_batch", self.base_url);
+        let rows: Vec<_> = intervals
+            .iter()
+            .map(|interval| {
+                let metrics = &interval.metrics;
+                serde_json::json!({
+                    "interval_id": interval.id,
+                    "started_at": interval.start,
+                    "ended_at": interval.end,
+                    "keypresses": metrics.keypresses,
+                    "mouse_clicks": metrics.mouse_clicks,
+                    "mouse_distance_in": metrics.mouse_distance_in,
+                    "mouse_distance_mi": metrics.mouse_distance_mi,
+                    "scroll_steps": metrics.scroll_steps,
+                    "left_clicks": metrics.left_clicks,
+                    "right_clicks": metrics.right_clicks,
+                    "middle_clicks": metrics.middle_clicks,
+                    "extra_clicks": metrics.extra_clicks,
+                    "double_clicks": metrics.double_clicks,
+                    "triple_clicks": metrics.triple_clicks,
+                    "drags": metrics.drags,
+                    "drag_distance_in": metrics.drag_distance_in
+                })
+            })
+            .collect();
         
         let response = self.client
             .post(&url)
             .header("Content-Type", "application/json")
             .json(&serde_json::json!({
-                "p_device_id": metrics.device_id,
-                "p_keypresses": metrics.keypresses,
-                "p_mouse_clicks": metrics.mouse_clicks,
-                "p_mouse_distance_in": metrics.mouse_distance_in,
-                "p_mouse_distance_mi": metrics.mouse_distance_mi,
-                "p_scroll_steps": metrics.scroll_steps
+                "p_device_id": self.device_id,
+                "p_intervals": rows
             }))
             .send()
             .await?;
@@ -78,47 +90,22 @@ impl SupabaseClient {
         let status = response.status();
         if !status.is_success() {
             let error_text = response.text().await?;
-            log::error!("Failed to upsert metrics. Status: {}, Error: {}", 
-                status, error_text);
-            anyhow::bail!("Supabase request failed: {}", error_text);
+            anyhow::bail!("Supabase request failed with {}: {}", status, error_text);
         }
     
-        Ok(())
+        let acknowledged: Vec<Acknowledged> = response.json().await
+            .context("Failed to parse Supabase acknowledgements")?;
+        Ok(acknowledged.into_iter().map(|row| row.interval_id).collect())
     }
+}
 
-    pub async fn update_metrics(&self, device_id: &str, metrics: &Metrics) -> Result<()> {
-        let url = format!("{}/rest/v1/kweeb_logger_metrics", self.base_url);
-        
-        self.client
-            .patch(&url)
-            .header("apikey", &self.api_key)
-            .header("Content-Type", "application/json")
-            .query(&[("device_id", "eq.".to_string() + device_id)])
-            .json(&metrics)
-            .send()
-            .await?;
-
-        Ok(())
+#[async_trait]
+impl RemoteSink for SupabaseClient {
+    fn name(&self) -> &str {
+        SUPABASE_SINK
     }
 
-    #[allow(dead_code)]
-    pub async fn get_total_metrics(&self, device_id: &str) -> Result<Metrics> {
-        let url = format!(
-            "{}/rest/v1/rpc/get_total_metrics",
-            self.base_url
-        );
-
-        let response = self.client
-            .post(&url)
-            .header("apikey", &self.api_key)
-            .header("Content-Type", "application/json")
-            .json(&serde_json::json!({
-                "p_device_id": device_id
-            }))
-            .send()
-            .await?;
-
-        let metrics = response.json::<Metrics>().await?;
-        Ok(metrics)
+    async fn send(&self, intervals: &[Interval]) -> Result<Vec<Uuid>> {
+        self.upsert_metrics_batch(intervals).await
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+// The delay after the first failed upload, doubling with each failure in a
+// row up to the maximum.
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+// After this many failures in a row the endpoint is treated as down and
+// left alone for the cooldown, after which a single upload is tried again.
+const FAILURE_THRESHOLD: u32 = 5;
+const COOLDOWN: Duration = Duration::from_secs(5 * 60);
+
+/// Decides how long to wait between uploads while they're failing: an
+/// exponential backoff with jitter, and once failures keep coming, a
+/// circuit breaker that pauses uploads for a cooldown.
+#[derive(Default)]
+pub struct CircuitBreaker {
+    failures: u32,
+}
+
+impl CircuitBreaker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether uploads are paused for the cooldown.
+    pub fn is_open(&self) -> bool {
+        self.failures >= FAILURE_THRESHOLD
+    }
+
+    /// Records a successful upload. Returns whether the circuit was open.
+    pub fn succeeded(&mut self) -> bool {
+        let was_open = self.is_open();
+        self.failures = 0;
+        was_open
+    }
+
+    /// Records a failed upload and returns how long to wait before the
+    /// next one.
+    pub fn failed(&mut self) -> Duration {
+        self.failures = self.failures.saturating_add(1);
+        if self.is_open() {
+            return COOLDOWN;
+        }
+
+        let delay = BASE_DELAY
+            .saturating_mul(1 << (self.failures - 1).min(16))
+            .min(MAX_DELAY);
+        // Anywhere in the upper half, so clients that failed together
+        // don't all retry together.
+        let millis = delay.as_millis() as u64;
+        Duration::from_millis(fastrand::u64(millis / 2..=millis))
+    }
+}
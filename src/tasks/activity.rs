@@ -0,0 +1,37 @@
+use std::sync::Arc;
+use chrono::Utc;
+use tokio::sync::watch;
+use tokio::time::{self, Duration};
+
+use crate::app::AppState;
+
+/// Closes sessions that have gone idle and writes sessions to the database,
+/// including the one in progress so its end time stays current. On
+/// shutdown the one in progress is closed and saved before returning.
+pub async fn save_activity_sessions(state: Arc<AppState>, mut shutdown: watch::Receiver<bool>) {
+    loop {
+        let stopping = tokio::select! {
+            _ = time::sleep(Duration::from_secs(30)) => false,
+            _ = shutdown.changed() => true,
+        };
+
+        let sessions = {
+            let mut activity = state.activity.lock().await;
+            if stopping {
+                activity.close();
+            } else {
+                activity.check_idle(Utc::now());
+            }
+            activity.take_sessions()
+        };
+
+        for session in &sessions {
+            if let Err(e) = state.db.save_session(session).await {
+                log::error!("Failed to save activity session: {}", e);
+            }
+        }
+        if stopping {
+            return;
+        }
+    }
+}
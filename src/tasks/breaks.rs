@@ -0,0 +1,109 @@
+use std::sync::Arc;
+use chrono::Utc;
+use tokio::sync::mpsc;
+use tokio::time::{self, Duration};
+
+use crate::app::AppState;
+use crate::breaks::{BreakEvent, BreakOutcome};
+use crate::config::BreakConfig;
+use crate::menubar::MenuAction;
+use crate::notify;
+
+/// Reminds the user to take breaks and records what they did about each
+/// reminder.
+pub async fn remind_breaks(state: Arc<AppState>, config: BreakConfig) {
+    let mut actions = state.menu_bar.lock().await.take_actions();
+    let mut interval = time::interval(Duration::from_secs(15));
+    // The reminder currently shown, if any.
+    let mut pending: Option<i64> = None;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let event = state.breaks.lock().await.check(Utc::now());
+                match event {
+                    Some(BreakEvent::Due { active_minutes }) => {
+                        pending = remind(&state, &config, active_minutes).await;
+                    }
+                    Some(BreakEvent::Taken) => {
+                        if let Some(id) = pending.take() {
+                            resolve(&state, id, BreakOutcome::Taken).await;
+                        }
+                    }
+                    None => {}
+                }
+            }
+            action = next_action(&mut actions) => {
+                let Some(action) = action else {
+                    actions = None;
+                    continue;
+                };
+                let (id, outcome) = match action {
+                    MenuAction::SnoozeBreak { id } => (id, BreakOutcome::Snoozed),
+                    MenuAction::SkipBreak { id } => (id, BreakOutcome::Skipped),
+                };
+                if pending != Some(id) {
+                    log::debug!("Ignoring {:?} for a reminder that is no longer shown", outcome);
+                    continue;
+                }
+                {
+                    let mut breaks = state.breaks.lock().await;
+                    match outcome {
+                        BreakOutcome::Snoozed => breaks.snooze(Utc::now()),
+                        _ => breaks.skip(Utc::now()),
+                    }
+                }
+                pending = None;
+                resolve(&state, id, outcome).await;
+            }
+        }
+    }
+}
+
+async fn next_action(actions: &mut Option<mpsc::UnboundedReceiver<MenuAction>>) -> Option<MenuAction> {
+    match actions {
+        Some(actions) => actions.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn remind(state: &AppState, config: &BreakConfig, active_minutes: f64) -> Option<i64> {
+    log::info!("Break due after {:.0} minutes of activity", active_minutes);
+
+    let id = match state.db.insert_break_reminder(Utc::now(), active_minutes).await {
+        Ok(id) => id,
+        Err(e) => {
+            log::error!("Failed to record break reminder: {}", e);
+            return None;
+        }
+    };
+
+    if let Err(e) = state.menu_bar.lock().await.show_break_reminder(id, active_minutes) {
+        log::error!("Failed to show break reminder in menubar: {}", e);
+    }
+
+    if config.desktop_notifications {
+        let body = format!(
+            "You've been active for {:.0} minutes. Take {} minutes away from the keyboard.",
+            active_minutes, config.break_minutes
+        );
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = notify::desktop_notification("Time for a break", &body) {
+                log::error!("Failed to show break notification: {}", e);
+            }
+        });
+    }
+
+    Some(id)
+}
+
+async fn resolve(state: &AppState, id: i64, outcome: BreakOutcome) {
+    log::info!("Break reminder {} {}", id, outcome.as_str());
+
+    if let Err(e) = state.db.resolve_break_reminder(id, outcome, Utc::now()).await {
+        log::error!("Failed to record break reminder outcome: {}", e);
+    }
+    if let Err(e) = state.menu_bar.lock().await.clear_break_reminder() {
+        log::error!("Failed to clear break reminder in menubar: {}", e);
+    }
+}
@@ -1,176 +1,269 @@
 use std::sync::Arc;
-use tokio::time::Duration;
-use device_query::{DeviceQuery, DeviceState};
+use chrono::Utc;
+use tokio::sync::watch;
 use crate::menubar::MenuMetrics;
-use crate::monitor::calculate_multi_monitor_distance;
-use crate::scroll::ScrollTracker;
+use crate::metrics::{Metrics, TypingSpeed};
+use crate::config::InputConfig;
+use crate::monitor::{calculate_distance, calculate_multi_monitor_distance, Monitor};
+use crate::input::{self, InputEvent, InputEventKind};
+use crate::input::compare::PollerComparison;
 use crate::app::AppState;
-use crate::supabase::SupabaseClient;
-use crate::supabase;
-use std::collections::HashSet;
+use crate::clicks::ClickTracker;
+use crate::db::Period;
+use crate::typing::TypingTracker;
+
+// How often unsaved counts are written to the journal, which bounds what a
+// crash can lose, and how often they're saved to the database.
+const CHECKPOINT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+const SAVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
 
 pub async fn save_metrics_with_updates(
     state: Arc<AppState>,
-    supabase: Option<Arc<SupabaseClient>>
+    sinks: Vec<String>,
+    mut shutdown: watch::Receiver<bool>,
 ) {
-    // Generate a device ID once at startup
-    let device_id = get_or_create_device_id();
-    log::info!("Starting metrics save loop with device_id: {}", device_id);
-    
     let mut last_ui_update = std::time::Instant::now();
     let min_ui_update_interval = std::time::Duration::from_secs(1);
+    let mut last_save = std::time::Instant::now();
+    let mut checkpoint = tokio::time::interval(CHECKPOINT_INTERVAL);
     
     loop {
-        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-        
-        let metrics = match tokio::time::timeout(
-            tokio::time::Duration::from_secs(1),
-            state.metrics.lock()
-        ).await {
-            Ok(guard) => guard,
-            Err(_) => {
-                log::warn!("Timeout while acquiring metrics lock");
-                continue;
-            }
+        let stopping = tokio::select! {
+            _ = checkpoint.tick() => false,
+            _ = shutdown.changed() => true,
         };
 
-        let metrics_data = metrics.clone();
-        drop(metrics);
-
-        if let Ok(_) = state.db.insert_metrics(
-            metrics_data.keypresses,
-            metrics_data.mouse_clicks,
-            metrics_data.mouse_distance_in,
-            metrics_data.mouse_distance_mi,
-            metrics_data.scroll_steps,
-        ).await {
-            log::debug!("Successfully saved metrics to local database");
-            
-            if let Some(supabase_client) = &supabase {
-                let supabase_metrics = supabase::Metrics {
-                    id: None,
-                    created_at: None,
-                    keypresses: metrics_data.keypresses,
-                    mouse_clicks: metrics_data.mouse_clicks,
-                    mouse_distance_in: metrics_data.mouse_distance_in,
-                    mouse_distance_mi: metrics_data.mouse_distance_mi,
-                    scroll_steps: metrics_data.scroll_steps,
-                    device_id: device_id.clone(),
-                };
-
-                log::debug!("Attempting to save metrics to Supabase: {:?}", supabase_metrics);
-                if let Err(e) = supabase_client.upsert_metrics(&supabase_metrics).await {
-                    log::error!("Failed to save metrics to Supabase: {}", e);
-                } else {
-                    log::debug!("Successfully saved metrics to Supabase");
-                }
-            } else {
-                log::debug!("Supabase client not configured, skipping remote save");
+        if !stopping && last_save.elapsed() < SAVE_INTERVAL {
+            write_journal(&state);
+            continue;
+        }
+        last_save = std::time::Instant::now();
+
+        // Everything counted up to here is in this interval and
+        // everything after goes into the next. Until it's saved, the
+        // journal still holds the last checkpoint of it.
+        let interval = state.metrics.take();
+
+        let saved = match state.db.insert_metrics(&interval, &sinks).await {
+            Ok(_) => {
+                log::debug!("Successfully saved metrics to local database");
+                true
+            }
+            Err(e) => {
+                log::error!("Failed to save metrics to local database: {}", e);
+                // Saved with the next interval instead.
+                state.metrics.restore(&interval);
+                false
             }
+        };
+        write_journal(&state);
+        if stopping {
+            return;
+        }
+        if !saved {
+            continue;
+        }
 
-            let now = std::time::Instant::now();
-            if now.duration_since(last_ui_update) >= min_ui_update_interval {
-                if let Ok(new_total) = state.db.get_total_metrics().await {
-                    if let Ok(mut total) = state.total_metrics.try_lock() {
-                        *total = new_total.clone();
-                        
-                        if let Ok(mut menu_bar) = state.menu_bar.try_lock() {
-                            let menu_metrics = MenuMetrics::new(
-                                new_total.total_keypresses,
-                                new_total.total_mouse_clicks,
-                                new_total.total_mouse_distance_in,
-                                new_total.total_mouse_distance_mi,
-                                new_total.total_scroll_steps,
-                            );
-                            
-                            if let Err(e) = menu_bar.update_metrics(&menu_metrics) {
-                                log::error!("Failed to update menu metrics: {}", e);
-                            }
-                        }
-                        
-                        last_ui_update = now;
+        let now = std::time::Instant::now();
+        if now.duration_since(last_ui_update) >= min_ui_update_interval {
+            if let Ok(new_total) = state.db.get_total_metrics().await {
+                *state.total_metrics.lock().await = new_total.clone();
+
+                if let Ok(mut menu_bar) = state.menu_bar.try_lock() {
+                    let (typing, active_minutes) = today(&state).await.unwrap_or_else(|e| {
+                        log::error!("Failed to get today's metrics: {}", e);
+                        Default::default()
+                    });
+                    let menu_metrics = MenuMetrics::from(&new_total).with_today(&typing, active_minutes);
+                    
+                    if let Err(e) = menu_bar.update_metrics(&menu_metrics) {
+                        log::error!("Failed to update menu metrics: {}", e);
                     }
                 }
-            }
-
-            if let Ok(mut metrics) = state.metrics.try_lock() {
-                metrics.reset();
+                
+                last_ui_update = now;
             }
         }
     }
 }
 
+// Typing speed and active minutes so far today.
+async fn today(state: &AppState) -> anyhow::Result<(TypingSpeed, f64)> {
+    let now = Utc::now();
+    let start = Period::Today.start(now, state.db.timezone());
+    let typing = state.db.get_range_totals(start, None).await?.typing_speed();
+    let active_minutes = state.db
+        .get_active_minutes_by_day(start, now)
+        .await?
+        .iter()
+        .map(|day| day.active_minutes)
+        .sum();
+    Ok((typing, active_minutes))
+}
 
-fn get_or_create_device_id() -> String {
-    let app_dirs = directories::ProjectDirs::from("com", "kweeb-logger", "logger")
-        .expect("Failed to get project directories");
-    let data_dir = app_dirs.data_dir();
-    let device_id_path = data_dir.join("device_id");
-
-    if let Ok(existing_id) = std::fs::read_to_string(&device_id_path) {
-        existing_id
-    } else {
-        let new_id = uuid::Uuid::new_v4().to_string();
-        std::fs::create_dir_all(data_dir).expect("Failed to create data directory");
-        std::fs::write(device_id_path, &new_id).expect("Failed to save device ID");
-        new_id
+fn write_journal(state: &AppState) {
+    if let Err(e) = state.journal.write(&state.metrics.snapshot()) {
+        log::error!("Failed to write metrics journal: {:#}", e);
     }
 }
 
-pub async fn collect_metrics(state: Arc<AppState>) {
-    let device_state = DeviceState::new();
-    let mut last_mouse = device_state.get_mouse();
-    let mut last_keys = device_state.get_keys();
-    let mut scroll_tracker = ScrollTracker::new();
+// Used for relative pointer motion when no monitor information is available.
+const DEFAULT_PPI: f64 = 96.0;
 
-    let mut previously_pressed: HashSet<bool> = last_mouse.button_pressed
-        .iter()
-        .copied()
-        .collect();
+/// Turns input events into metric deltas. This is the only place events are
+/// counted, whichever `InputSource` produced them.
+pub struct MetricsAccumulator {
+    last_pointer: Option<(i32, i32)>,
+    clicks: ClickTracker,
+    typing: TypingTracker,
+}
 
-    loop {
-        tokio::time::sleep(Duration::from_millis(100)).await;
-
-        let current_mouse = device_state.get_mouse();
-        let current_keys = device_state.get_keys();
-        let scroll_delta = scroll_tracker.get_scroll_delta();
-
-        let distance = calculate_multi_monitor_distance(
-            last_mouse.coords.0,
-            last_mouse.coords.1,
-            current_mouse.coords.0,
-            current_mouse.coords.1,
-            &state.monitors.lock().await,
-        ).unwrap_or(0.0);
-
-        let mut click_count = 0;
-        for (prev, curr) in last_mouse.button_pressed.iter().zip(current_mouse.button_pressed.iter()) {
-            if !prev && *curr {
-                click_count += 1;
+impl MetricsAccumulator {
+    pub fn new() -> Self {
+        Self {
+            last_pointer: None,
+            clicks: ClickTracker::new(),
+            typing: TypingTracker::new(),
+        }
+    }
+
+    pub fn apply(&mut self, event: &InputEvent, monitors: &[Monitor], metrics: &mut Metrics) {
+        match event.kind {
+            InputEventKind::KeyDown(key) => {
+                metrics.keypresses += 1;
+                *metrics.key_counts.entry(key).or_insert(0) += 1;
+                self.typing.key_down(event.time, metrics);
+            }
+            InputEventKind::ButtonDown(button) => {
+                self.clicks.button_down(button, event.time, metrics);
+            }
+            InputEventKind::ButtonUp(button) => {
+                self.clicks.button_up(button, metrics);
+            }
+            InputEventKind::PointerMoved { x, y } => {
+                if let Some((last_x, last_y)) = self.last_pointer {
+                    let distance = calculate_multi_monitor_distance(last_x, last_y, x, y, monitors)
+                        .unwrap_or(0.0);
+                    metrics.mouse_distance_in += distance;
+                    metrics.mouse_distance_mi += distance / 63360.0;
+                }
+                self.last_pointer = Some((x, y));
+                self.clicks.pointer_moved((x, y), monitors);
+            }
+            InputEventKind::PointerDelta { dx, dy } => {
+                let ppi = monitors.first().map(|m| m.ppi).unwrap_or(DEFAULT_PPI);
+                let distance = calculate_distance(0, 0, dx, dy) / ppi;
+                metrics.mouse_distance_in += distance;
+                metrics.mouse_distance_mi += distance / 63360.0;
+                self.clicks.pointer_delta(dx, dy, distance);
             }
+            InputEventKind::Scroll { delta_x, delta_y, precise_x, precise_y } => {
+                let (delta_x, delta_y) = (i64::from(delta_x), i64::from(delta_y));
+                if delta_y > 0 {
+                    metrics.scroll_up += delta_y;
+                } else {
+                    metrics.scroll_down -= delta_y;
+                }
+                if delta_x > 0 {
+                    metrics.scroll_right += delta_x;
+                } else {
+                    metrics.scroll_left -= delta_x;
+                }
+                metrics.scroll_steps += delta_x.abs() + delta_y.abs();
+                metrics.scroll_vertical_precise += precise_y.abs();
+                metrics.scroll_horizontal_precise += precise_x.abs();
+            }
+            InputEventKind::KeyUp(_) => {}
         }
+    }
+}
+
+pub async fn collect_metrics(state: Arc<AppState>, input_config: InputConfig) {
+    let mut comparison = input_config.compare_with_poller.then(PollerComparison::start);
+    let mut events = input::spawn(move || input::open(&input_config));
+    let mut accumulator = MetricsAccumulator::new();
 
-        if let Ok(mut metrics) = state.metrics.try_lock() {
-            metrics.keypresses += current_keys.iter()
-                .filter(|k| !last_keys.contains(k))
-                .count() as i32;
+    while let Some(event) = events.recv().await {
+        let mut delta = Metrics::default();
+        let monitors = state.monitors.lock().await;
+        let mut activity = state.activity.lock().await;
+        let mut breaks = state.breaks.lock().await;
+        let mut next = Some(event);
+        while let Some(event) = next {
+            accumulator.apply(&event, &monitors, &mut delta);
+            activity.record(event.time);
+            breaks.record(event.time);
+            if let Some(comparison) = comparison.as_mut() {
+                comparison.record(&event);
+            }
+            next = events.try_recv().ok();
+        }
+        drop(breaks);
+        drop(activity);
+        drop(monitors);
 
-            metrics.mouse_clicks += click_count;
-            metrics.mouse_distance_in += distance;
-            metrics.mouse_distance_mi += distance / 63360.0;
-            metrics.scroll_steps += scroll_delta;
+        if let Some(comparison) = comparison.as_mut() {
+            comparison.report_if_due();
         }
 
-        if let Ok(mut total) = state.total_metrics.try_lock() {
-            total.total_keypresses += current_keys.iter()
-                .filter(|k| !last_keys.contains(k))
-                .count() as i32;
+        state.metrics.add(&delta);
+        state.total_metrics.lock().await.add(&delta);
+    }
 
-            total.total_mouse_clicks += click_count;
-            total.total_scroll_steps += scroll_delta;
+    log::warn!("Input event stream closed, stopping metrics collection");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::scripted::ScriptedSource;
+    use crate::input::{InputSource, MouseButton};
+    use chrono::{Duration, TimeZone, Utc};
+    use device_query::Keycode;
+
+    fn replay(events: Vec<InputEvent>) -> Metrics {
+        let mut source = ScriptedSource::new(events);
+        let mut accumulator = MetricsAccumulator::new();
+        let mut metrics = Metrics::default();
+        while let Some(event) = source.next_event().unwrap() {
+            accumulator.apply(&event, &[], &mut metrics);
         }
+        metrics
+    }
 
-        last_mouse = current_mouse;
-        last_keys = current_keys;
+    #[test]
+    fn scripted_events_are_counted() {
+        let start = Utc.with_ymd_and_hms(2026, 3, 2, 9, 0, 0).unwrap();
+        let at = |ms: i64, kind: InputEventKind| InputEvent { time: start + Duration::milliseconds(ms), kind };
+        let metrics = replay(vec![
+            at(0, InputEventKind::KeyDown(Keycode::A)),
+            at(50, InputEventKind::KeyUp(Keycode::A)),
+            at(100, InputEventKind::KeyDown(Keycode::B)),
+            at(150, InputEventKind::KeyDown(Keycode::A)),
+            at(1000, InputEventKind::ButtonDown(MouseButton::Left)),
+            at(1050, InputEventKind::ButtonUp(MouseButton::Left)),
+            at(1200, InputEventKind::ButtonDown(MouseButton::Left)),
+            at(1250, InputEventKind::ButtonUp(MouseButton::Left)),
+            at(3000, InputEventKind::ButtonDown(MouseButton::Right)),
+            at(3050, InputEventKind::ButtonUp(MouseButton::Right)),
+            // 3-4-5 inches at the default 96 PPI.
+            at(4000, InputEventKind::PointerDelta { dx: 288, dy: 384 }),
+            at(5000, InputEventKind::Scroll { delta_x: 0, delta_y: -3, precise_x: 0.0, precise_y: -3.0 }),
+            at(5100, InputEventKind::Scroll { delta_x: 1, delta_y: 0, precise_x: 0.5, precise_y: 0.0 }),
+        ]);
+
+        assert_eq!(metrics.keypresses, 3);
+        assert_eq!(metrics.key_counts[&Keycode::A], 2);
+        assert_eq!(metrics.key_counts[&Keycode::B], 1);
+        assert_eq!(metrics.mouse_clicks, 3);
+        assert_eq!(metrics.left_clicks, 2);
+        assert_eq!(metrics.right_clicks, 1);
+        assert_eq!(metrics.double_clicks, 1);
+        assert!((metrics.mouse_distance_in - 5.0).abs() < 1e-9);
+        assert_eq!(metrics.scroll_down, 3);
+        assert_eq!(metrics.scroll_right, 1);
+        assert_eq!(metrics.scroll_steps, 4);
+        assert!((metrics.scroll_vertical_precise - 3.0).abs() < 1e-9);
+        assert!((metrics.scroll_horizontal_precise - 0.5).abs() < 1e-9);
     }
-}
\ No newline at end of file
+}
@@ -1,2 +1,6 @@
+pub mod activity;
+pub mod breaks;
 pub mod metrics;
 pub mod monitor;
+pub mod retention;
+pub mod sync;
@@ -0,0 +1,136 @@
+use std::sync::Arc;
+use chrono::{DateTime, Months, Utc};
+use tokio::time::{self, Duration};
+
+use crate::app::AppState;
+use crate::config::RetentionConfig;
+use crate::db::Granularity;
+
+/// Deletes raw intervals after `raw_days` and hourly rollups after
+/// `hourly_months`, where 0 keeps them, then vacuums the database if
+/// anything went. Their totals live on in the coarser rollups, and the
+/// daily rollups are kept forever. Whole UTC days go at a time, so history
+/// is either raw, hourly or daily per day.
+pub async fn enforce_retention(state: Arc<AppState>, config: RetentionConfig) {
+    let mut interval = time::interval(Duration::from_secs(60 * 60));
+    loop {
+        interval.tick().await;
+
+        let now = Utc::now();
+        let raw_days = config.raw_days();
+        let mut compacted = 0;
+        if let Some(cutoff) = raw_cutoff(now, raw_days) {
+            match state.db.compact(cutoff).await {
+                Ok(removed) => compacted = removed,
+                Err(e) => log::error!("Failed to compact metrics: {}", e),
+            }
+        }
+
+        let mut pruned = 0;
+        if let Some(cutoff) = hourly_cutoff(now, config.hourly_months) {
+            match state.db.prune_hourly(cutoff).await {
+                Ok(removed) => pruned = removed,
+                Err(e) => log::error!("Failed to prune hourly rollups: {}", e),
+            }
+        }
+
+        if compacted == 0 && pruned == 0 {
+            continue;
+        }
+        if let Err(e) = state.db.vacuum().await {
+            log::error!("Failed to vacuum database: {}", e);
+        }
+        log::info!(
+            "Retention removed {} raw intervals older than {} days and {} hourly rollups older than {} months",
+            compacted,
+            raw_days,
+            pruned,
+            config.hourly_months,
+        );
+    }
+}
+
+// The start of the UTC day `raw_days` before `now`, before which raw
+// intervals go, or None if they're kept.
+fn raw_cutoff(now: DateTime<Utc>, raw_days: u32) -> Option<DateTime<Utc>> {
+    (raw_days > 0).then(|| Granularity::Daily.bucket_start(now - chrono::Duration::days(raw_days as i64)))
+}
+
+// The start of the UTC day `hourly_months` before `now`, before which
+// hourly rollups go, or None if they're kept.
+fn hourly_cutoff(now: DateTime<Utc>, hourly_months: u32) -> Option<DateTime<Utc>> {
+    if hourly_months == 0 {
+        return None;
+    }
+    let cutoff = now.checked_sub_months(Months::new(hourly_months))?;
+    Some(Granularity::Daily.bucket_start(cutoff))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing::open_sqlite;
+    use crate::db::Storage;
+    use crate::metrics::{Interval, Metrics};
+    use uuid::Uuid;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn cutoffs_fall_on_utc_midnight() {
+        let now = utc("2026-03-10T15:30:00Z");
+        assert_eq!(raw_cutoff(now, 7), Some(utc("2026-03-03T00:00:00Z")));
+        assert_eq!(raw_cutoff(now, 0), None);
+        assert_eq!(hourly_cutoff(now, 12), Some(utc("2025-03-10T00:00:00Z")));
+        assert_eq!(hourly_cutoff(utc("2026-03-31T08:00:00Z"), 1), Some(utc("2026-02-28T00:00:00Z")));
+        assert_eq!(hourly_cutoff(now, 0), None);
+    }
+
+    #[tokio::test]
+    async fn cutoff_removes_only_older_rows() {
+        let (db, _file) = open_sqlite().await;
+        let ends = [
+            "2026-03-02T10:00:00Z",
+            "2026-03-02T23:59:59Z",
+            "2026-03-03T00:00:00Z",
+            "2026-03-03T00:00:05Z",
+        ];
+        for end in ends {
+            let end = utc(end);
+            let interval = Interval {
+                id: Uuid::new_v4(),
+                start: end - chrono::Duration::seconds(5),
+                end,
+                metrics: Metrics { keypresses: 1, ..Default::default() },
+            };
+            db.insert_metrics(&interval, &[]).await.unwrap();
+        }
+
+        let now = utc("2026-03-10T15:30:00Z");
+        let (start, end) = (utc("2026-03-01T00:00:00Z"), utc("2026-03-04T00:00:00Z"));
+        assert_eq!(db.compact(raw_cutoff(now, 7).unwrap()).await.unwrap(), 2);
+        let raw: Vec<_> = db.get_metric_rows(start, end, Granularity::Raw).await.unwrap()
+            .into_iter()
+            .map(|(time, _)| time)
+            .collect();
+        assert_eq!(raw, [utc(ends[2]), utc(ends[3])]);
+
+        // The hourly rollups of the compacted day are still there until
+        // they're old enough to go themselves.
+        let hourly = db.get_metric_rows(start, end, Granularity::Hourly).await.unwrap();
+        assert_eq!(hourly.len(), 3);
+        let later = utc("2027-03-03T12:00:00Z");
+        assert_eq!(db.prune_hourly(hourly_cutoff(later, 12).unwrap()).await.unwrap(), 2);
+        let hourly: Vec<_> = db.get_metric_rows(start, end, Granularity::Hourly).await.unwrap()
+            .into_iter()
+            .map(|(time, metrics)| (time, metrics.keypresses))
+            .collect();
+        assert_eq!(hourly, [(utc("2026-03-03T00:00:00Z"), 2)]);
+
+        let daily = db.get_metric_rows(start, end, Granularity::Daily).await.unwrap();
+        assert_eq!(daily.len(), 2);
+        assert_eq!(db.get_total_metrics().await.unwrap().total_keypresses, 4);
+    }
+}
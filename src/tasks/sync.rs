@@ -0,0 +1,281 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use anyhow::Result;
+use tokio::time::Duration;
+use uuid::Uuid;
+
+use crate::app::AppState;
+use crate::config::SyncConfig;
+use crate::db::Storage;
+use crate::metrics::Interval;
+use crate::sink::RemoteSink;
+use crate::sync::CircuitBreaker;
+
+// An interval the sink has answered for this many times without
+// acknowledging it is dead-lettered, so one it can't take doesn't hold up
+// the rest of the queue.
+const MAX_REJECTIONS: i64 = 10;
+
+/// Uploads the intervals queued for `sink` in the sync outbox in batches,
+/// oldest first. An interval leaves the outbox only once the sink has
+/// acknowledged it, so nothing is lost while the endpoint is unreachable.
+pub async fn sync_outbox(state: Arc<AppState>, sink: Arc<dyn RemoteSink>, config: SyncConfig) {
+    log::info!("Starting sync to {}", sink.name());
+
+    let flush_interval = Duration::from_secs(config.flush_interval_secs);
+    let max_batch_size = config.max_batch_size.max(1);
+    let mut breaker = CircuitBreaker::new();
+    loop {
+        let delay = match sync_pending(state.db.as_ref(), sink.as_ref(), max_batch_size, &mut breaker).await {
+            Ok(delay) => delay.unwrap_or(flush_interval),
+            Err(e) => {
+                log::error!("Failed to sync metrics outbox to {}: {:#}", sink.name(), e);
+                flush_interval
+            }
+        };
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Uploads batches until the outbox is empty or a request fails, and in that
+/// case returns how long to wait before trying again. Intervals the server
+/// didn't acknowledge wait for the next flush, up to `MAX_REJECTIONS` times.
+pub async fn sync_pending(
+    db: &dyn Storage,
+    sink: &dyn RemoteSink,
+    max_batch_size: u32,
+    breaker: &mut CircuitBreaker,
+) -> Result<Option<Duration>> {
+    loop {
+        let entries = db.pending_sync(sink.name(), max_batch_size as i64).await?;
+        if entries.is_empty() {
+            return Ok(None);
+        }
+
+        let intervals: Vec<Interval> = entries.iter().map(|entry| entry.interval.clone()).collect();
+        let acknowledged: HashSet<Uuid> = match sink.send(&intervals).await {
+            Ok(ids) => ids.into_iter().collect(),
+            Err(e) => {
+                let error = format!("{:#}", e);
+                for entry in &entries {
+                    db.record_sync_failure(entry.id, &error).await?;
+                }
+
+                let was_open = breaker.is_open();
+                let delay = breaker.failed();
+                if breaker.is_open() && !was_open {
+                    log::warn!("Sync to {} keeps failing, pausing for {:?}: {}", sink.name(), delay, error);
+                } else {
+                    log::debug!(
+                        "Failed to save {} intervals to {}, retrying in {:?}: {}",
+                        entries.len(), sink.name(), delay, error
+                    );
+                }
+                return Ok(Some(delay));
+            }
+        };
+        if breaker.succeeded() {
+            log::info!("Sync to {} resumed", sink.name());
+        }
+
+        let mut rejected = 0;
+        for entry in &entries {
+            if acknowledged.contains(&entry.interval.id) {
+                db.complete_sync(entry.id).await?;
+            } else {
+                let dead_letter = entry.rejections + 1 >= MAX_REJECTIONS;
+                let error = format!("Not acknowledged by {}", sink.name());
+                db.record_sync_rejection(entry.id, &error, dead_letter).await?;
+                if dead_letter {
+                    log::warn!(
+                        "{} didn't acknowledge interval {} after {} tries, giving up on it",
+                        sink.name(), entry.interval.id, MAX_REJECTIONS
+                    );
+                } else {
+                    log::debug!(
+                        "{} didn't acknowledge interval {} (attempt {})",
+                        sink.name(), entry.interval.id, entry.attempts + 1
+                    );
+                }
+                rejected += 1;
+            }
+        }
+        log::debug!("Saved {} intervals to {}", entries.len() - rejected, sink.name());
+
+        if rejected > 0 {
+            log::warn!(
+                "{} didn't acknowledge {} of {} intervals, retrying them later",
+                sink.name(), rejected, entries.len()
+            );
+            return Ok(None);
+        }
+        if entries.len() < max_batch_size as usize {
+            return Ok(None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing::open_sqlite;
+    use crate::metrics::Metrics;
+    use crate::supabase::{SupabaseClient, SUPABASE_SINK};
+    use chrono::Utc;
+    use std::sync::Mutex;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    // How the stand-in for PostgREST answers the next request.
+    #[derive(Default)]
+    struct Script {
+        failures: u32,
+        skip: Option<Uuid>,
+        requests: u32,
+    }
+
+    // Serves `upsert_metrics_batch`, acknowledging every interval it's sent
+    // except `skip`, or failing with a 503 while `failures` lasts.
+    async fn serve(script: Arc<Mutex<Script>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    stream.read_line(&mut line).await.unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                stream.read_exact(&mut body).await.unwrap();
+                let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+                let (status, reply) = {
+                    let mut script = script.lock().unwrap();
+                    script.requests += 1;
+                    if script.failures > 0 {
+                        script.failures -= 1;
+                        ("503 Service Unavailable", "{}".to_string())
+                    } else {
+                        let acknowledged: Vec<_> = body["p_intervals"]
+                            .as_array()
+                            .unwrap()
+                            .iter()
+                            .map(|row| &row["interval_id"])
+                            .filter(|id| Some(id.as_str().unwrap().parse().unwrap()) != script.skip)
+                            .map(|id| serde_json::json!({ "interval_id": id }))
+                            .collect();
+                        ("200 OK", serde_json::Value::from(acknowledged).to_string())
+                    }
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    reply.len(),
+                    reply
+                );
+                stream.get_mut().write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        url
+    }
+
+    // Saves `count` intervals queued for Supabase.
+    async fn queue(db: &dyn Storage, count: usize) -> Vec<Uuid> {
+        let mut ids = Vec::new();
+        for _ in 0..count {
+            let now = Utc::now();
+            let interval = Interval {
+                id: Uuid::new_v4(),
+                start: now - chrono::Duration::seconds(5),
+                end: now,
+                metrics: Metrics { keypresses: 10, ..Default::default() },
+            };
+            db.insert_metrics(&interval, &[SUPABASE_SINK.to_string()]).await.unwrap();
+            ids.push(interval.id);
+        }
+        ids
+    }
+
+    async fn pending_ids(db: &dyn Storage) -> Vec<Uuid> {
+        let entries = db.pending_sync(SUPABASE_SINK, 100).await.unwrap();
+        entries.iter().map(|entry| entry.interval.id).collect()
+    }
+
+    #[tokio::test]
+    async fn outbox_survives_outage_until_acknowledged() {
+        let (db, _file) = open_sqlite().await;
+        let ids = queue(&db, 3).await;
+        let script = Arc::new(Mutex::new(Script { failures: 5, ..Default::default() }));
+        let url = serve(script.clone()).await;
+        let sink = SupabaseClient::new(&url, "key", "test-device".to_string()).unwrap();
+        let mut breaker = CircuitBreaker::new();
+
+        // Backoff doubles from a second, with jitter in the upper half.
+        for failure in 0..4 {
+            let delay = sync_pending(&db, &sink, 10, &mut breaker).await.unwrap().unwrap();
+            let ceiling = Duration::from_secs(1 << failure);
+            assert!(delay >= ceiling / 2 && delay <= ceiling, "delay {:?} after {} failures", delay, failure + 1);
+            assert!(!breaker.is_open());
+            assert_eq!(pending_ids(&db).await, ids);
+        }
+
+        // The fifth failure in a row opens the breaker for the cooldown.
+        let delay = sync_pending(&db, &sink, 10, &mut breaker).await.unwrap().unwrap();
+        assert!(breaker.is_open());
+        assert!(delay > Duration::from_secs(60));
+        let entries = db.pending_sync(SUPABASE_SINK, 100).await.unwrap();
+        assert!(entries.iter().all(|entry| entry.attempts == 5));
+
+        // Once it's back, only what it acknowledged leaves the outbox.
+        script.lock().unwrap().skip = Some(ids[1]);
+        assert_eq!(sync_pending(&db, &sink, 10, &mut breaker).await.unwrap(), None);
+        assert!(!breaker.is_open());
+        assert_eq!(pending_ids(&db).await, vec![ids[1]]);
+
+        script.lock().unwrap().skip = None;
+        assert_eq!(sync_pending(&db, &sink, 10, &mut breaker).await.unwrap(), None);
+        assert!(pending_ids(&db).await.is_empty());
+        assert_eq!(script.lock().unwrap().requests, 7);
+
+        // Nothing left to send, so nothing is sent.
+        assert_eq!(sync_pending(&db, &sink, 10, &mut breaker).await.unwrap(), None);
+        assert_eq!(script.lock().unwrap().requests, 7);
+    }
+
+    #[tokio::test]
+    async fn rejected_interval_is_dead_lettered() {
+        let (db, _file) = open_sqlite().await;
+        let ids = queue(&db, 3).await;
+        let script = Arc::new(Mutex::new(Script { skip: Some(ids[0]), ..Default::default() }));
+        let url = serve(script.clone()).await;
+        let sink = SupabaseClient::new(&url, "key", "test-device".to_string()).unwrap();
+        let mut breaker = CircuitBreaker::new();
+
+        for _ in 1..MAX_REJECTIONS {
+            assert_eq!(sync_pending(&db, &sink, 10, &mut breaker).await.unwrap(), None);
+            assert_eq!(pending_ids(&db).await, vec![ids[0]]);
+        }
+        assert_eq!(sync_pending(&db, &sink, 10, &mut breaker).await.unwrap(), None);
+        assert!(pending_ids(&db).await.is_empty());
+        assert!(!breaker.is_open());
+
+        // It's no longer sent, and doesn't hold up what's queued after it.
+        let requests = script.lock().unwrap().requests;
+        assert_eq!(requests, MAX_REJECTIONS as u32);
+        queue(&db, 1).await;
+        assert_eq!(sync_pending(&db, &sink, 10, &mut breaker).await.unwrap(), None);
+        assert_eq!(script.lock().unwrap().requests, requests + 1);
+        assert!(pending_ids(&db).await.is_empty());
+    }
+}
@@ -0,0 +1,116 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::VecDeque;
+
+use crate::metrics::Metrics;
+
+// A pause longer than this between two keys ends a typing burst. Time
+// outside bursts doesn't count towards the typing rate.
+const BURST_GAP_MS: i64 = 2000;
+
+// An average English word plus the space after it, the usual WPM convention.
+pub const KEYS_PER_WORD: f64 = 5.0;
+
+/// Measures typing rate from key presses: the rate within bursts, and the
+/// peak rate sustained over the trailing 1 and 5 minutes.
+#[derive(Default)]
+pub struct TypingTracker {
+    last_key: Option<DateTime<Utc>>,
+    // Key presses from the last five minutes.
+    recent: VecDeque<DateTime<Utc>>,
+}
+
+impl TypingTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn key_down(&mut self, time: DateTime<Utc>, metrics: &mut Metrics) {
+        if let Some(last) = self.last_key {
+            let gap = time - last;
+            if gap >= Duration::zero() && gap <= Duration::milliseconds(BURST_GAP_MS) {
+                metrics.typing_keys += 1;
+                metrics.typing_seconds += gap.num_milliseconds() as f64 / 1000.0;
+            }
+        }
+        self.last_key = Some(time);
+
+        self.recent.push_back(time);
+        while let Some(&oldest) = self.recent.front() {
+            if time - oldest < Duration::minutes(5) {
+                break;
+            }
+            self.recent.pop_front();
+        }
+
+        let last_minute = self
+            .recent
+            .iter()
+            .rev()
+            .take_while(|&&key| time - key < Duration::minutes(1))
+            .count();
+        metrics.peak_kpm_1m = metrics.peak_kpm_1m.max(last_minute as f64);
+        metrics.peak_kpm_5m = metrics.peak_kpm_5m.max(self.recent.len() as f64 / 5.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(ms: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 2, 9, 0, 0).unwrap() + Duration::milliseconds(ms)
+    }
+
+    #[test]
+    fn pauses_split_bursts() {
+        let mut tracker = TypingTracker::new();
+        let mut metrics = Metrics::default();
+        for ms in [0, 200, 400, 400 + BURST_GAP_MS + 1, 400 + BURST_GAP_MS + 301] {
+            tracker.key_down(at(ms), &mut metrics);
+        }
+
+        assert_eq!(metrics.typing_keys, 3);
+        assert!((metrics.typing_seconds - 0.7).abs() < 1e-9);
+        assert!((metrics.typing_kpm() - 3.0 * 60.0 / 0.7).abs() < 1e-9);
+    }
+
+    #[test]
+    fn one_minute_peak_counts_keys_in_the_trailing_minute() {
+        let mut tracker = TypingTracker::new();
+        let mut metrics = Metrics::default();
+        // 120 keys in the first minute, then one a second.
+        for i in 0..120 {
+            tracker.key_down(at(i * 500), &mut metrics);
+        }
+        assert_eq!(metrics.peak_kpm_1m, 120.0);
+        for i in 0..120 {
+            tracker.key_down(at(60_000 + i * 1000), &mut metrics);
+        }
+        assert_eq!(metrics.peak_kpm_1m, 120.0);
+
+        // The peak of a later interval only sees the slower window.
+        let mut later = Metrics::default();
+        tracker.key_down(at(180_000), &mut later);
+        assert_eq!(later.peak_kpm_1m, 60.0);
+    }
+
+    #[test]
+    fn five_minute_peak_averages_the_trailing_five_minutes() {
+        let mut tracker = TypingTracker::new();
+        let mut metrics = Metrics::default();
+        // A burst of 100 keys in ten seconds.
+        for i in 0..100 {
+            tracker.key_down(at(i * 100), &mut metrics);
+        }
+        assert_eq!(metrics.peak_kpm_5m, 20.0);
+        assert_eq!(metrics.peak_kpm_1m, 100.0);
+
+        // Five minutes after the burst started, its first key has dropped
+        // out of the window.
+        let mut later = Metrics::default();
+        tracker.key_down(at(300_000), &mut later);
+        assert_eq!(later.peak_kpm_5m, 100.0 / 5.0);
+        assert_eq!(later.peak_kpm_1m, 1.0);
+    }
+}
@@ -0,0 +1,66 @@
+-- Adds the breakdown of clicks by button, multi-clicks and drags to the
+-- shared dashboard.
+--
+-- upsert_metrics gains an argument for each new counter. They default to 0,
+-- so clients that only send the original counters keep working, and the
+-- six-argument version is dropped so PostgREST has a single function to
+-- choose.
+
+ALTER TABLE kweeb_logger_metrics
+    ADD COLUMN left_clicks INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN right_clicks INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN middle_clicks INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN extra_clicks INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN double_clicks INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN triple_clicks INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN drags INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN drag_distance_in DOUBLE PRECISION NOT NULL DEFAULT 0;
+
+DROP FUNCTION IF EXISTS upsert_metrics(
+    TEXT, INTEGER, INTEGER, DOUBLE PRECISION, DOUBLE PRECISION, INTEGER
+);
+
+-- Adds one interval's counts to the device's running totals.
+CREATE FUNCTION upsert_metrics(
+    p_device_id TEXT,
+    p_keypresses INTEGER,
+    p_mouse_clicks INTEGER,
+    p_mouse_distance_in DOUBLE PRECISION,
+    p_mouse_distance_mi DOUBLE PRECISION,
+    p_scroll_steps INTEGER,
+    p_left_clicks INTEGER DEFAULT 0,
+    p_right_clicks INTEGER DEFAULT 0,
+    p_middle_clicks INTEGER DEFAULT 0,
+    p_extra_clicks INTEGER DEFAULT 0,
+    p_double_clicks INTEGER DEFAULT 0,
+    p_triple_clicks INTEGER DEFAULT 0,
+    p_drags INTEGER DEFAULT 0,
+    p_drag_distance_in DOUBLE PRECISION DEFAULT 0
+) RETURNS VOID
+LANGUAGE sql
+AS $$
+    INSERT INTO kweeb_logger_metrics (
+        device_id, keypresses, mouse_clicks, mouse_distance_in, mouse_distance_mi, scroll_steps,
+        left_clicks, right_clicks, middle_clicks, extra_clicks,
+        double_clicks, triple_clicks, drags, drag_distance_in
+    )
+    VALUES (
+        p_device_id, p_keypresses, p_mouse_clicks, p_mouse_distance_in, p_mouse_distance_mi, p_scroll_steps,
+        p_left_clicks, p_right_clicks, p_middle_clicks, p_extra_clicks,
+        p_double_clicks, p_triple_clicks, p_drags, p_drag_distance_in
+    )
+    ON CONFLICT (device_id) DO UPDATE SET
+        keypresses = kweeb_logger_metrics.keypresses + excluded.keypresses,
+        mouse_clicks = kweeb_logger_metrics.mouse_clicks + excluded.mouse_clicks,
+        mouse_distance_in = kweeb_logger_metrics.mouse_distance_in + excluded.mouse_distance_in,
+        mouse_distance_mi = kweeb_logger_metrics.mouse_distance_mi + excluded.mouse_distance_mi,
+        scroll_steps = kweeb_logger_metrics.scroll_steps + excluded.scroll_steps,
+        left_clicks = kweeb_logger_metrics.left_clicks + excluded.left_clicks,
+        right_clicks = kweeb_logger_metrics.right_clicks + excluded.right_clicks,
+        middle_clicks = kweeb_logger_metrics.middle_clicks + excluded.middle_clicks,
+        extra_clicks = kweeb_logger_metrics.extra_clicks + excluded.extra_clicks,
+        double_clicks = kweeb_logger_metrics.double_clicks + excluded.double_clicks,
+        triple_clicks = kweeb_logger_metrics.triple_clicks + excluded.triple_clicks,
+        drags = kweeb_logger_metrics.drags + excluded.drags,
+        drag_distance_in = kweeb_logger_metrics.drag_distance_in + excluded.drag_distance_in;
+$$;
@@ -0,0 +1,69 @@
+-- Widens the shared dashboard's counters to 64 bits. Totals summed across
+-- devices overflow a 32-bit integer long before any one device does.
+--
+-- Postgres can't change a function's argument types in place, and keeping
+-- the old integer overload next to a bigint one makes PostgREST's choice
+-- ambiguous, so the integer upsert_metrics from 20261015000000_click_counters
+-- is dropped and created again.
+
+ALTER TABLE kweeb_logger_metrics
+    ALTER COLUMN keypresses TYPE BIGINT,
+    ALTER COLUMN mouse_clicks TYPE BIGINT,
+    ALTER COLUMN scroll_steps TYPE BIGINT,
+    ALTER COLUMN left_clicks TYPE BIGINT,
+    ALTER COLUMN right_clicks TYPE BIGINT,
+    ALTER COLUMN middle_clicks TYPE BIGINT,
+    ALTER COLUMN extra_clicks TYPE BIGINT,
+    ALTER COLUMN double_clicks TYPE BIGINT,
+    ALTER COLUMN triple_clicks TYPE BIGINT,
+    ALTER COLUMN drags TYPE BIGINT;
+
+DROP FUNCTION IF EXISTS upsert_metrics(
+    TEXT, INTEGER, INTEGER, DOUBLE PRECISION, DOUBLE PRECISION, INTEGER,
+    INTEGER, INTEGER, INTEGER, INTEGER, INTEGER, INTEGER, INTEGER, DOUBLE PRECISION
+);
+
+-- Adds one interval's counts to the device's running totals.
+CREATE FUNCTION upsert_metrics(
+    p_device_id TEXT,
+    p_keypresses BIGINT,
+    p_mouse_clicks BIGINT,
+    p_mouse_distance_in DOUBLE PRECISION,
+    p_mouse_distance_mi DOUBLE PRECISION,
+    p_scroll_steps BIGINT,
+    p_left_clicks BIGINT DEFAULT 0,
+    p_right_clicks BIGINT DEFAULT 0,
+    p_middle_clicks BIGINT DEFAULT 0,
+    p_extra_clicks BIGINT DEFAULT 0,
+    p_double_clicks BIGINT DEFAULT 0,
+    p_triple_clicks BIGINT DEFAULT 0,
+    p_drags BIGINT DEFAULT 0,
+    p_drag_distance_in DOUBLE PRECISION DEFAULT 0
+) RETURNS VOID
+LANGUAGE sql
+AS $$
+    INSERT INTO kweeb_logger_metrics (
+        device_id, keypresses, mouse_clicks, mouse_distance_in, mouse_distance_mi, scroll_steps,
+        left_clicks, right_clicks, middle_clicks, extra_clicks,
+        double_clicks, triple_clicks, drags, drag_distance_in
+    )
+    VALUES (
+        p_device_id, p_keypresses, p_mouse_clicks, p_mouse_distance_in, p_mouse_distance_mi, p_scroll_steps,
+        p_left_clicks, p_right_clicks, p_middle_clicks, p_extra_clicks,
+        p_double_clicks, p_triple_clicks, p_drags, p_drag_distance_in
+    )
+    ON CONFLICT (device_id) DO UPDATE SET
+        keypresses = kweeb_logger_metrics.keypresses + excluded.keypresses,
+        mouse_clicks = kweeb_logger_metrics.mouse_clicks + excluded.mouse_clicks,
+        mouse_distance_in = kweeb_logger_metrics.mouse_distance_in + excluded.mouse_distance_in,
+        mouse_distance_mi = kweeb_logger_metrics.mouse_distance_mi + excluded.mouse_distance_mi,
+        scroll_steps = kweeb_logger_metrics.scroll_steps + excluded.scroll_steps,
+        left_clicks = kweeb_logger_metrics.left_clicks + excluded.left_clicks,
+        right_clicks = kweeb_logger_metrics.right_clicks + excluded.right_clicks,
+        middle_clicks = kweeb_logger_metrics.middle_clicks + excluded.middle_clicks,
+        extra_clicks = kweeb_logger_metrics.extra_clicks + excluded.extra_clicks,
+        double_clicks = kweeb_logger_metrics.double_clicks + excluded.double_clicks,
+        triple_clicks = kweeb_logger_metrics.triple_clicks + excluded.triple_clicks,
+        drags = kweeb_logger_metrics.drags + excluded.drags,
+        drag_distance_in = kweeb_logger_metrics.drag_distance_in + excluded.drag_distance_in;
+$$;
@@ -0,0 +1,101 @@
+-- Makes uploads idempotent. Each interval now arrives with a client-generated
+-- id and the span of time it covers, and is recorded in
+-- kweeb_logger_intervals. Only an interval seen for the first time is added
+-- to the device's totals, so a retried or duplicated upload changes nothing.
+--
+-- The id-less upsert_metrics is dropped so an old client can't keep adding
+-- to the totals unchecked; its uploads fail and stay queued until it's
+-- updated.
+
+CREATE TABLE kweeb_logger_intervals (
+    interval_id UUID PRIMARY KEY,
+    device_id TEXT NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    ended_at TIMESTAMPTZ NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    keypresses BIGINT NOT NULL DEFAULT 0,
+    mouse_clicks BIGINT NOT NULL DEFAULT 0,
+    mouse_distance_in DOUBLE PRECISION NOT NULL DEFAULT 0,
+    mouse_distance_mi DOUBLE PRECISION NOT NULL DEFAULT 0,
+    scroll_steps BIGINT NOT NULL DEFAULT 0,
+    left_clicks BIGINT NOT NULL DEFAULT 0,
+    right_clicks BIGINT NOT NULL DEFAULT 0,
+    middle_clicks BIGINT NOT NULL DEFAULT 0,
+    extra_clicks BIGINT NOT NULL DEFAULT 0,
+    double_clicks BIGINT NOT NULL DEFAULT 0,
+    triple_clicks BIGINT NOT NULL DEFAULT 0,
+    drags BIGINT NOT NULL DEFAULT 0,
+    drag_distance_in DOUBLE PRECISION NOT NULL DEFAULT 0
+);
+
+CREATE INDEX idx_kweeb_logger_intervals_device ON kweeb_logger_intervals (device_id, started_at);
+
+DROP FUNCTION IF EXISTS upsert_metrics(
+    TEXT, BIGINT, BIGINT, DOUBLE PRECISION, DOUBLE PRECISION, BIGINT,
+    BIGINT, BIGINT, BIGINT, BIGINT, BIGINT, BIGINT, BIGINT, DOUBLE PRECISION
+);
+
+-- Records one interval and, unless it was already recorded, adds its counts
+-- to the device's running totals.
+CREATE FUNCTION upsert_metrics(
+    p_device_id TEXT,
+    p_interval_id UUID,
+    p_started_at TIMESTAMPTZ,
+    p_ended_at TIMESTAMPTZ,
+    p_keypresses BIGINT,
+    p_mouse_clicks BIGINT,
+    p_mouse_distance_in DOUBLE PRECISION,
+    p_mouse_distance_mi DOUBLE PRECISION,
+    p_scroll_steps BIGINT,
+    p_left_clicks BIGINT DEFAULT 0,
+    p_right_clicks BIGINT DEFAULT 0,
+    p_middle_clicks BIGINT DEFAULT 0,
+    p_extra_clicks BIGINT DEFAULT 0,
+    p_double_clicks BIGINT DEFAULT 0,
+    p_triple_clicks BIGINT DEFAULT 0,
+    p_drags BIGINT DEFAULT 0,
+    p_drag_distance_in DOUBLE PRECISION DEFAULT 0
+) RETURNS VOID
+LANGUAGE sql
+AS $$
+    WITH new_interval AS (
+        INSERT INTO kweeb_logger_intervals (
+            interval_id, device_id, started_at, ended_at,
+            keypresses, mouse_clicks, mouse_distance_in, mouse_distance_mi, scroll_steps,
+            left_clicks, right_clicks, middle_clicks, extra_clicks,
+            double_clicks, triple_clicks, drags, drag_distance_in
+        )
+        VALUES (
+            p_interval_id, p_device_id, p_started_at, p_ended_at,
+            p_keypresses, p_mouse_clicks, p_mouse_distance_in, p_mouse_distance_mi, p_scroll_steps,
+            p_left_clicks, p_right_clicks, p_middle_clicks, p_extra_clicks,
+            p_double_clicks, p_triple_clicks, p_drags, p_drag_distance_in
+        )
+        ON CONFLICT (interval_id) DO NOTHING
+        RETURNING *
+    )
+    INSERT INTO kweeb_logger_metrics (
+        device_id, keypresses, mouse_clicks, mouse_distance_in, mouse_distance_mi, scroll_steps,
+        left_clicks, right_clicks, middle_clicks, extra_clicks,
+        double_clicks, triple_clicks, drags, drag_distance_in
+    )
+    SELECT
+        device_id, keypresses, mouse_clicks, mouse_distance_in, mouse_distance_mi, scroll_steps,
+        left_clicks, right_clicks, middle_clicks, extra_clicks,
+        double_clicks, triple_clicks, drags, drag_distance_in
+    FROM new_interval
+    ON CONFLICT (device_id) DO UPDATE SET
+        keypresses = kweeb_logger_metrics.keypresses + excluded.keypresses,
+        mouse_clicks = kweeb_logger_metrics.mouse_clicks + excluded.mouse_clicks,
+        mouse_distance_in = kweeb_logger_metrics.mouse_distance_in + excluded.mouse_distance_in,
+        mouse_distance_mi = kweeb_logger_metrics.mouse_distance_mi + excluded.mouse_distance_mi,
+        scroll_steps = kweeb_logger_metrics.scroll_steps + excluded.scroll_steps,
+        left_clicks = kweeb_logger_metrics.left_clicks + excluded.left_clicks,
+        right_clicks = kweeb_logger_metrics.right_clicks + excluded.right_clicks,
+        middle_clicks = kweeb_logger_metrics.middle_clicks + excluded.middle_clicks,
+        extra_clicks = kweeb_logger_metrics.extra_clicks + excluded.extra_clicks,
+        double_clicks = kweeb_logger_metrics.double_clicks + excluded.double_clicks,
+        triple_clicks = kweeb_logger_metrics.triple_clicks + excluded.triple_clicks,
+        drags = kweeb_logger_metrics.drags + excluded.drags,
+        drag_distance_in = kweeb_logger_metrics.drag_distance_in + excluded.drag_distance_in;
+$$;
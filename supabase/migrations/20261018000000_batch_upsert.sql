@@ -0,0 +1,43 @@
+-- Uploads many intervals in one request. Each interval is recorded as
+-- upsert_metrics would record it, on its own, so one bad interval doesn't
+-- hold back the rest. The ids returned are the intervals now recorded,
+-- including any recorded before; the client retries the others.
+
+CREATE FUNCTION upsert_metrics_batch(
+    p_device_id TEXT,
+    p_intervals JSONB
+) RETURNS TABLE (interval_id UUID)
+LANGUAGE plpgsql
+AS $$
+DECLARE
+    item JSONB;
+BEGIN
+    FOR item IN SELECT * FROM jsonb_array_elements(p_intervals) LOOP
+        BEGIN
+            PERFORM upsert_metrics(
+                p_device_id,
+                (item->>'interval_id')::UUID,
+                (item->>'started_at')::TIMESTAMPTZ,
+                (item->>'ended_at')::TIMESTAMPTZ,
+                (item->>'keypresses')::BIGINT,
+                (item->>'mouse_clicks')::BIGINT,
+                (item->>'mouse_distance_in')::DOUBLE PRECISION,
+                (item->>'mouse_distance_mi')::DOUBLE PRECISION,
+                (item->>'scroll_steps')::BIGINT,
+                COALESCE((item->>'left_clicks')::BIGINT, 0),
+                COALESCE((item->>'right_clicks')::BIGINT, 0),
+                COALESCE((item->>'middle_clicks')::BIGINT, 0),
+                COALESCE((item->>'extra_clicks')::BIGINT, 0),
+                COALESCE((item->>'double_clicks')::BIGINT, 0),
+                COALESCE((item->>'triple_clicks')::BIGINT, 0),
+                COALESCE((item->>'drags')::BIGINT, 0),
+                COALESCE((item->>'drag_distance_in')::DOUBLE PRECISION, 0)
+            );
+            interval_id := (item->>'interval_id')::UUID;
+            RETURN NEXT;
+        EXCEPTION WHEN OTHERS THEN
+            RAISE WARNING 'Skipping interval %: %', item->>'interval_id', SQLERRM;
+        END;
+    END LOOP;
+END;
+$$;
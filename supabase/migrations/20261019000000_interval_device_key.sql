@@ -0,0 +1,73 @@
+-- Keys recorded intervals by device as well as id. With the id alone, an
+-- interval whose id another device had already used was taken for a
+-- duplicate: it was acknowledged but never added to the totals. Now each
+-- device only ever collides with its own uploads.
+
+ALTER TABLE kweeb_logger_intervals
+    DROP CONSTRAINT kweeb_logger_intervals_pkey,
+    ADD PRIMARY KEY (device_id, interval_id);
+
+-- Records one interval and, unless the device already recorded it, adds its
+-- counts to the device's running totals.
+CREATE OR REPLACE FUNCTION upsert_metrics(
+    p_device_id TEXT,
+    p_interval_id UUID,
+    p_started_at TIMESTAMPTZ,
+    p_ended_at TIMESTAMPTZ,
+    p_keypresses BIGINT,
+    p_mouse_clicks BIGINT,
+    p_mouse_distance_in DOUBLE PRECISION,
+    p_mouse_distance_mi DOUBLE PRECISION,
+    p_scroll_steps BIGINT,
+    p_left_clicks BIGINT DEFAULT 0,
+    p_right_clicks BIGINT DEFAULT 0,
+    p_middle_clicks BIGINT DEFAULT 0,
+    p_extra_clicks BIGINT DEFAULT 0,
+    p_double_clicks BIGINT DEFAULT 0,
+    p_triple_clicks BIGINT DEFAULT 0,
+    p_drags BIGINT DEFAULT 0,
+    p_drag_distance_in DOUBLE PRECISION DEFAULT 0
+) RETURNS VOID
+LANGUAGE sql
+AS $$
+    WITH new_interval AS (
+        INSERT INTO kweeb_logger_intervals (
+            interval_id, device_id, started_at, ended_at,
+            keypresses, mouse_clicks, mouse_distance_in, mouse_distance_mi, scroll_steps,
+            left_clicks, right_clicks, middle_clicks, extra_clicks,
+            double_clicks, triple_clicks, drags, drag_distance_in
+        )
+        VALUES (
+            p_interval_id, p_device_id, p_started_at, p_ended_at,
+            p_keypresses, p_mouse_clicks, p_mouse_distance_in, p_mouse_distance_mi, p_scroll_steps,
+            p_left_clicks, p_right_clicks, p_middle_clicks, p_extra_clicks,
+            p_double_clicks, p_triple_clicks, p_drags, p_drag_distance_in
+        )
+        ON CONFLICT (device_id, interval_id) DO NOTHING
+        RETURNING *
+    )
+    INSERT INTO kweeb_logger_metrics (
+        device_id, keypresses, mouse_clicks, mouse_distance_in, mouse_distance_mi, scroll_steps,
+        left_clicks, right_clicks, middle_clicks, extra_clicks,
+        double_clicks, triple_clicks, drags, drag_distance_in
+    )
+    SELECT
+        device_id, keypresses, mouse_clicks, mouse_distance_in, mouse_distance_mi, scroll_steps,
+        left_clicks, right_clicks, middle_clicks, extra_clicks,
+        double_clicks, triple_clicks, drags, drag_distance_in
+    FROM new_interval
+    ON CONFLICT (device_id) DO UPDATE SET
+        keypresses = kweeb_logger_metrics.keypresses + excluded.keypresses,
+        mouse_clicks = kweeb_logger_metrics.mouse_clicks + excluded.mouse_clicks,
+        mouse_distance_in = kweeb_logger_metrics.mouse_distance_in + excluded.mouse_distance_in,
+        mouse_distance_mi = kweeb_logger_metrics.mouse_distance_mi + excluded.mouse_distance_mi,
+        scroll_steps = kweeb_logger_metrics.scroll_steps + excluded.scroll_steps,
+        left_clicks = kweeb_logger_metrics.left_clicks + excluded.left_clicks,
+        right_clicks = kweeb_logger_metrics.right_clicks + excluded.right_clicks,
+        middle_clicks = kweeb_logger_metrics.middle_clicks + excluded.middle_clicks,
+        extra_clicks = kweeb_logger_metrics.extra_clicks + excluded.extra_clicks,
+        double_clicks = kweeb_logger_metrics.double_clicks + excluded.double_clicks,
+        triple_clicks = kweeb_logger_metrics.triple_clicks + excluded.triple_clicks,
+        drags = kweeb_logger_metrics.drags + excluded.drags,
+        drag_distance_in = kweeb_logger_metrics.drag_distance_in + excluded.drag_distance_in;
+$$;